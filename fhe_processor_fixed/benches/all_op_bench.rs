@@ -23,26 +23,26 @@ fn safe_const_for(op: &ArithmeticOp) -> usize {
     }
 }
 
-fn make_manager(op: ArithmeticOp, operand: OperandType) -> (OperationManager, usize /*out_idx*/) {
-    let mut manager = OperationManager::new(*SetI, 10, 8);
+fn make_manager(op: ArithmeticOp, operand: OperandType) -> (OperationManager<u64>, usize /*out_idx*/) {
+    let mut manager = OperationManager::new(*SetI, 10, 8).unwrap();
     let unary = is_unary(&op);
 
     match operand {
         OperandType::BothCipher => {
             if unary {
-                manager.add_operation(op, OperandType::CipherPlain, None);
-                manager.set_execution_plan(vec![Step::new(0, vec![0], 1)]);
+                manager.add_operation(op, OperandType::CipherPlain, None).unwrap();
+                manager.set_execution_plan(vec![Step::new(0, vec![0], 1)]).unwrap();
                 (manager, 1)
             } else {
-                manager.add_operation(op, OperandType::BothCipher, None);
-                manager.set_execution_plan(vec![Step::new(0, vec![0, 1], 2)]);
+                manager.add_operation(op, OperandType::BothCipher, None).unwrap();
+                manager.set_execution_plan(vec![Step::new(0, vec![0, 1], 2)]).unwrap();
                 (manager, 2)
             }
         }
         OperandType::PlainCipher | OperandType::CipherPlain => {
             let k = safe_const_for(&op);
-            manager.add_operation(op, operand, Some(k));
-            manager.set_execution_plan(vec![Step::new(0, vec![0], 1)]);
+            manager.add_operation(op, operand, Some(k)).unwrap();
+            manager.set_execution_plan(vec![Step::new(0, vec![0], 1)]).unwrap();
             (manager, 1)
         }
     }
@@ -77,22 +77,22 @@ fn bench_one_combo(c: &mut Criterion, label: &str, op: ArithmeticOp, operand: Op
                 match operand {
                     OperandType::BothCipher => {
                         if is_unary(&op) {
-                            manager.load_data(a as usize, 0);
+                            manager.load_data(a as usize, 0).unwrap();
                         } else {
-                            manager.load_data(a as usize, 0);
-                            manager.load_data(bval as usize, 1);
+                            manager.load_data(a as usize, 0).unwrap();
+                            manager.load_data(bval as usize, 1).unwrap();
                         }
                     }
                     OperandType::PlainCipher | OperandType::CipherPlain => {
-                        manager.load_data(a as usize, 0);
+                        manager.load_data(a as usize, 0).unwrap();
                     }
                 }
 
                 let t0 = Instant::now();
-                manager.execute();
+                manager.execute().unwrap();
                 total += t0.elapsed();
 
-                black_box(manager.get_data(out_idx));
+                black_box(manager.get_data(out_idx).unwrap());
             }
             total
         });
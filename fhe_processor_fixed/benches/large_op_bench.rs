@@ -40,16 +40,16 @@ fn run_case(c: &mut Criterion, case: &BenchCase) {
         OperandType::CipherPlain => 1,
         _ => panic!("Only BothCipher and CipherPlain supported here"),
     };
-    let mut manager = OperationManager::new(*SetII, slots + 1, case.bit_len);
+    let mut manager = OperationManager::new(*SetII, slots + 1, case.bit_len).unwrap();
 
     match case.mode {
         OperandType::BothCipher => {
-            manager.add_operation(case.op, OperandType::BothCipher, None);
-            manager.set_execution_plan(vec![Step::new(0, vec![0, 1], slots)]);
+            manager.add_operation(case.op, OperandType::BothCipher, None).unwrap();
+            manager.set_execution_plan(vec![Step::new(0, vec![0, 1], slots)]).unwrap();
         }
         OperandType::CipherPlain => {
-            manager.add_operation(case.op, OperandType::CipherPlain, case.plain_val);
-            manager.set_execution_plan(vec![Step::new(0, vec![0], slots)]);
+            manager.add_operation(case.op, OperandType::CipherPlain, case.plain_val).unwrap();
+            manager.set_execution_plan(vec![Step::new(0, vec![0], slots)]).unwrap();
         }
         _ => {}
     }
@@ -66,20 +66,20 @@ fn run_case(c: &mut Criterion, case: &BenchCase) {
                 match case.mode {
                     OperandType::BothCipher => {
                         let b: usize = rng.gen::<u32>() as usize & mask;
-                        manager.load_data(a.cast_into(), 0);
-                        manager.load_data(b.cast_into(), 1);
+                        manager.load_data(a.cast_into(), 0).unwrap();
+                        manager.load_data(b.cast_into(), 1).unwrap();
                     }
                     OperandType::CipherPlain => {
-                        manager.load_data(a.cast_into(), 0);
+                        manager.load_data(a.cast_into(), 0).unwrap();
                     }
                     _ => {}
                 }
 
                 let start = Instant::now();
-                manager.execute();
+                manager.execute().unwrap();
                 total += start.elapsed();
 
-                let _ = black_box(manager.get_data(slots));
+                let _ = black_box(manager.get_data(slots).unwrap());
             }
 
             total
@@ -165,14 +165,15 @@ fn run_cbs_part(ctx: &BenchCtx, prep: &mut IterSetup) {
                 ss_key_view,
                 ksk,
                 params,
-            );
+            )
+            .unwrap();
         });
 
     prep.fourier_ggsw_lists = ggsw_lists;
 }
 
 fn run_lut_part(ctx: &BenchCtx, prep: &mut IterSetup, n_bits: usize) {
-    let input_bits = concat_ggsw_lists(prep.fourier_ggsw_lists.clone(), true);
+    let input_bits = concat_ggsw_lists(prep.fourier_ggsw_lists.clone(), true).unwrap();
     let ggsw_view = input_bits.as_view();
     let group_size = prep.pack_size.min(n_bits / ctx.params.extract_size());
     let lut_size = 1_usize << n_bits;
@@ -4,24 +4,24 @@ use fhe_processor::{operations::manager::OperationManager, programs::{average::A
 
 
 fn bench_manager_execute_maximum(c: &mut Criterion) {
-    let mut manager = OperationManager::new(*SetI, 6, 8);
-    manager.add_operatoins(MaximumProgram::load_operatonis());
-    manager.set_execution_plan(MaximumProgram::load_programs(5));
+    let mut manager = OperationManager::new(*SetI, 6, 8).unwrap();
+    manager.add_operatoins(MaximumProgram::load_operatonis()).unwrap();
+    manager.set_execution_plan(MaximumProgram::load_programs(5)).unwrap();
 
-    manager.load_data(16, 0);
-    manager.load_data(4, 1);
-    manager.load_data(0, 2);
-    manager.load_data(9, 3);
-    manager.load_data(5, 4);
+    manager.load_data(16, 0).unwrap();
+    manager.load_data(4, 1).unwrap();
+    manager.load_data(0, 2).unwrap();
+    manager.load_data(9, 3).unwrap();
+    manager.load_data(5, 4).unwrap();
 
     c.bench_function("Maximum_Program", |b| {
         b.iter_custom(|iters| {
             let mut total = Duration::ZERO;
             for _ in 0..iters {
                 let start = Instant::now();
-                manager.execute();
+                manager.execute().unwrap();
                 total += start.elapsed();
-                black_box(manager.get_data(5));
+                black_box(manager.get_data(5).unwrap());
             }
             total
         })
@@ -29,24 +29,24 @@ fn bench_manager_execute_maximum(c: &mut Criterion) {
 }
 
 fn bench_manager_execute_bubble(c: &mut Criterion) {
-    let mut manager = OperationManager::new(*SetI, 6, 8);
-    manager.add_operatoins(BubbleProgram::load_operatonis());
-    manager.set_execution_plan(BubbleProgram::load_programs(5));
+    let mut manager = OperationManager::new(*SetI, 6, 8).unwrap();
+    manager.add_operatoins(BubbleProgram::load_operatonis()).unwrap();
+    manager.set_execution_plan(BubbleProgram::load_programs(5)).unwrap();
 
-    manager.load_data(16, 0);
-    manager.load_data(4, 1);
-    manager.load_data(0, 2);
-    manager.load_data(9, 3);
-    manager.load_data(5, 4);
+    manager.load_data(16, 0).unwrap();
+    manager.load_data(4, 1).unwrap();
+    manager.load_data(0, 2).unwrap();
+    manager.load_data(9, 3).unwrap();
+    manager.load_data(5, 4).unwrap();
 
     c.bench_function("Bubble_Program", |b| {
         b.iter_custom(|iters| {
             let mut total = Duration::ZERO;
             for _ in 0..iters {
                 let start = Instant::now();
-                manager.execute();
+                manager.execute().unwrap();
                 total += start.elapsed();
-                black_box(manager.get_data(0));
+                black_box(manager.get_data(0).unwrap());
             }
             total
         })
@@ -54,27 +54,27 @@ fn bench_manager_execute_bubble(c: &mut Criterion) {
 }
 
 fn bench_manager_execute_squaresum(c: &mut Criterion) {
-    let mut manager = OperationManager::new(*SetI, 7, 8);
-    manager.add_operatoins(SquaresumProgram::load_operatonis());
-    manager.set_execution_plan(SquaresumProgram::load_programs(5));
+    let mut manager = OperationManager::new(*SetI, 7, 8).unwrap();
+    manager.add_operatoins(SquaresumProgram::load_operatonis()).unwrap();
+    manager.set_execution_plan(SquaresumProgram::load_programs(5)).unwrap();
 
-    manager.load_data(2, 0);
-    manager.load_data(4, 1);
-    manager.load_data(0, 2);
-    manager.load_data(9, 3);
-    manager.load_data(5, 4);
+    manager.load_data(2, 0).unwrap();
+    manager.load_data(4, 1).unwrap();
+    manager.load_data(0, 2).unwrap();
+    manager.load_data(9, 3).unwrap();
+    manager.load_data(5, 4).unwrap();
 
     c.bench_function("Squaresum_Program", |b| {
         b.iter_custom(|iters| {
             let mut total = Duration::ZERO;
             for _ in 0..iters {
-                manager.load_data(0, 6);
+                manager.load_data(0, 6).unwrap();
 
                 let start = Instant::now();
-                manager.execute();
+                manager.execute().unwrap();
                 total += start.elapsed();
 
-                black_box(manager.get_data(6));
+                black_box(manager.get_data(6).unwrap());
             }
             total
         })
@@ -82,27 +82,27 @@ fn bench_manager_execute_squaresum(c: &mut Criterion) {
 }
 
 fn bench_manager_execute_average(c: &mut Criterion) {
-    let mut manager = OperationManager::new(*SetI, 6, 8);
-    manager.add_operatoins(AverageProgram::load_operatonis(5));
-    manager.set_execution_plan(AverageProgram::load_programs(5));
+    let mut manager = OperationManager::new(*SetI, 6, 8).unwrap();
+    manager.add_operatoins(AverageProgram::load_operatonis(5)).unwrap();
+    manager.set_execution_plan(AverageProgram::load_programs(5)).unwrap();
 
-    manager.load_data(2, 0);
-    manager.load_data(4, 1);
-    manager.load_data(0, 2);
-    manager.load_data(9, 3);
-    manager.load_data(5, 4);
+    manager.load_data(2, 0).unwrap();
+    manager.load_data(4, 1).unwrap();
+    manager.load_data(0, 2).unwrap();
+    manager.load_data(9, 3).unwrap();
+    manager.load_data(5, 4).unwrap();
 
     c.bench_function("Average_Program", |b| {
         b.iter_custom(|iters| {
             let mut total = Duration::ZERO;
             for _ in 0..iters {
-                manager.load_data(0, 5);
+                manager.load_data(0, 5).unwrap();
 
                 let start = Instant::now();
-                manager.execute();
+                manager.execute().unwrap();
                 total += start.elapsed();
 
-                black_box(manager.get_data(5));
+                black_box(manager.get_data(5).unwrap());
             }
             total
         })
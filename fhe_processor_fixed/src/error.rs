@@ -0,0 +1,72 @@
+use thiserror::Error;
+
+use crate::operations::operand::ArithmeticOp;
+
+/// Errors returned by the processor API in place of a panic.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ProcessorError {
+    #[error("unsupported bit width {0}, only 8, 16 and 32 are allowed")]
+    UnsupportedBitWidth(usize),
+
+    #[error("unsupported data length {0}, only 8, 16 and 32 bits are allowed")]
+    UnsupportedDataLength(usize),
+
+    #[error("{0:?} needs an immediate operand")]
+    MissingImmediate(ArithmeticOp),
+
+    #[error("operation index {index} out of bounds, {len} operations are registered")]
+    OperationIndexOutOfBounds { index: usize, len: usize },
+
+    #[error("memory slot {index} out of bounds, the memory has {len} slots")]
+    MemoryIndexOutOfBounds { index: usize, len: usize },
+
+    #[error("{op:?} expects {expected} input(s), the step gives {found}")]
+    OperandCountMismatch {
+        op: ArithmeticOp,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("{found} LWE blocks for {expected} chunks")]
+    ChunkCountMismatch { expected: usize, found: usize },
+
+    #[error("the GGSW list can not be empty")]
+    EmptyGgswList,
+
+    #[error("missing automorphism key for X -> X^{0}")]
+    MissingAutomorphismKey(usize),
+
+    #[error("invalid execution plan: {}", join_issues(.0))]
+    InvalidPlan(Vec<PlanIssue>),
+
+    #[error("memory slot {0} is written twice by one step")]
+    DuplicateOutput(usize),
+
+    #[error("slot indexed by loop level {level}, but only {depth} loop(s) enclose it")]
+    UnboundLoopIndex { level: usize, depth: usize },
+
+    #[error("a predicated block writes its condition slot {0}")]
+    ConditionWritten(usize),
+
+    #[error("scratch slot {0} of a predicated block is also used by its body")]
+    ScratchOverlap(usize),
+
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+}
+
+/// A problem of one step of an execution plan.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("step {step}: {error}")]
+pub struct PlanIssue {
+    pub step: usize,
+    pub error: ProcessorError,
+}
+
+fn join_issues(issues: &[PlanIssue]) -> String {
+    issues
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
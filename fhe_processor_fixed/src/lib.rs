@@ -1,5 +1,6 @@
 #[global_allocator]
 static GLOBAL: jemallocator::Jemalloc = jemallocator::Jemalloc;
+pub mod error;
 pub mod operations;
 pub mod opmized_operations;
 pub mod processors;
@@ -15,58 +16,158 @@ mod manager_tests {
     use tfhe::core_crypto::prelude::CastInto;
 
     use crate::{
+        error::{PlanIssue, ProcessorError},
         operations::{
-            manager::{OperationManager, Step},
-            operand::ArithmeticOp,
+            control::{PlanNode, Slot, StepTemplate},
+            manager::{dependency_levels, split_bits_high_to_low, OperationManager, Step},
+            operand::{ArithmeticOp, Signedness},
             operation::OperandType,
         },
         programs::{
-            average::AverageProgram, bubble::BubbleProgram, maximum::MaximumProgram,
-            squaresum::SquaresumProgram,
+            average::AverageProgram, bubble::BubbleProgram, builder::ProgramBuilder,
+            maximum::MaximumProgram, squaresum::SquaresumProgram,
         },
-        utils::instance::{SetI, SetII},
+        utils::instance::{SetI, SetI32, SetII},
     };
     const SAMPLE_SIZE: usize = 10;
     #[test]
     fn test_manager_maximum() {
         let size = 5_usize;
-        let mut manager = OperationManager::new(*SetI, size + 1, 8);
-        manager.add_operatoins(MaximumProgram::load_operatonis());
-        manager.set_execution_plan(MaximumProgram::load_programs(size));
-
-        manager.load_data(16, 0);
-        manager.load_data(4, 1);
-        manager.load_data(0, 2);
-        manager.load_data(9, 3);
-        manager.load_data(5, 4);
+        let mut manager = OperationManager::new(*SetI, size + 1, 8).unwrap();
+        manager
+            .add_operatoins(MaximumProgram::load_operatonis())
+            .unwrap();
+        manager
+            .set_execution_plan(MaximumProgram::load_programs(size))
+            .unwrap();
+
+        manager.load_data(16, 0).unwrap();
+        manager.load_data(4, 1).unwrap();
+        manager.load_data(0, 2).unwrap();
+        manager.load_data(9, 3).unwrap();
+        manager.load_data(5, 4).unwrap();
         let t = Instant::now();
         for _ in 0..SAMPLE_SIZE {
-            manager.execute();
+            manager.execute().unwrap();
         }
 
         println!(
             "Execution time: {:.3?}",
             t.elapsed() / SAMPLE_SIZE.cast_into()
         );
-        let result = manager.get_data(size);
+        let result = manager.get_data(size).unwrap();
         println!("Maximum of [16, 4, 0, 9, 5] is {}", result);
     }
 
+    #[test]
+    fn test_manager_maximum_u32() {
+        let size = 5_usize;
+        let mut manager = OperationManager::new(*SetI32, size + 1, 8).unwrap();
+        manager
+            .add_operatoins(MaximumProgram::load_operatonis())
+            .unwrap();
+        manager
+            .set_execution_plan(MaximumProgram::load_programs(size))
+            .unwrap();
+
+        manager.load_data(16, 0).unwrap();
+        manager.load_data(4, 1).unwrap();
+        manager.load_data(0, 2).unwrap();
+        manager.load_data(9, 3).unwrap();
+        manager.load_data(5, 4).unwrap();
+        manager.execute().unwrap();
+
+        let result = manager.get_data(size).unwrap();
+        println!("Maximum of [16, 4, 0, 9, 5] over u32 is {}", result);
+        assert_eq!(result, 16);
+    }
+
+    #[test]
+    fn test_manager_rejects_bad_program() {
+        for data_len in [0, 12, 64] {
+            assert_eq!(
+                OperationManager::new(*SetI, 2, data_len).err(),
+                Some(ProcessorError::UnsupportedDataLength(data_len))
+            );
+        }
+        // a full usize is split without overflowing the mask
+        assert_eq!(split_bits_high_to_low(usize::MAX, 64, 4), vec![0xf; 16]);
+
+        let mut manager = OperationManager::new(*SetI, 2, 8).unwrap();
+        assert_eq!(
+            manager.add_operation(ArithmeticOp::Div, OperandType::CipherPlain, None),
+            Err(ProcessorError::MissingImmediate(ArithmeticOp::Div))
+        );
+        manager
+            .add_operation(ArithmeticOp::Add, OperandType::BothCipher, None)
+            .unwrap();
+        manager
+            .add_operation(ArithmeticOp::SIGN, OperandType::CipherPlain, None)
+            .unwrap();
+        let plan = vec![
+            Step::new(0, vec![0, 1], 1),
+            Step::new(2, vec![0, 1], 1),
+            Step::new(0, vec![0], 1),
+            Step::new(1, vec![0], 3),
+        ];
+        assert_eq!(
+            manager.set_execution_plan(plan),
+            Err(ProcessorError::InvalidPlan(vec![
+                PlanIssue {
+                    step: 1,
+                    error: ProcessorError::OperationIndexOutOfBounds { index: 2, len: 2 },
+                },
+                PlanIssue {
+                    step: 2,
+                    error: ProcessorError::OperandCountMismatch {
+                        op: ArithmeticOp::Add,
+                        expected: 2,
+                        found: 1,
+                    },
+                },
+                PlanIssue {
+                    step: 3,
+                    error: ProcessorError::MissingImmediate(ArithmeticOp::SIGN),
+                },
+                PlanIssue {
+                    step: 3,
+                    error: ProcessorError::MemoryIndexOutOfBounds { index: 3, len: 2 },
+                },
+            ]))
+        );
+        assert!(manager
+            .validate_plan(&[Step::new(0, vec![0, 1], 1)])
+            .is_ok());
+        assert_eq!(
+            manager.load_data(3, 2),
+            Err(ProcessorError::MemoryIndexOutOfBounds { index: 2, len: 2 })
+        );
+        assert_eq!(
+            manager.get_data(5),
+            Err(ProcessorError::MemoryIndexOutOfBounds { index: 5, len: 2 })
+        );
+        assert!(manager.execution_plan.is_empty());
+    }
+
     #[test]
     fn test_manager_bubble() {
         let size = 5_usize;
-        let mut manager = OperationManager::new(*SetI, size + 1, 8);
-        manager.add_operatoins(BubbleProgram::load_operatonis());
-        manager.set_execution_plan(BubbleProgram::load_programs(size));
-
-        manager.load_data(16, 0);
-        manager.load_data(4, 1);
-        manager.load_data(0, 2);
-        manager.load_data(9, 3);
-        manager.load_data(5, 4);
+        let mut manager = OperationManager::new(*SetI, size + 1, 8).unwrap();
+        manager
+            .add_operatoins(BubbleProgram::load_operatonis())
+            .unwrap();
+        manager
+            .set_execution_plan(BubbleProgram::load_programs(size))
+            .unwrap();
+
+        manager.load_data(16, 0).unwrap();
+        manager.load_data(4, 1).unwrap();
+        manager.load_data(0, 2).unwrap();
+        manager.load_data(9, 3).unwrap();
+        manager.load_data(5, 4).unwrap();
         let t = Instant::now();
         for _ in 0..SAMPLE_SIZE {
-            manager.execute();
+            manager.execute().unwrap();
         }
 
         println!(
@@ -75,36 +176,197 @@ mod manager_tests {
         );
         print!("[16, 4, 0, 9, 5] after sorting is: [");
         for i in 0..size {
-            let result = manager.get_data(i);
+            let result = manager.get_data(i).unwrap();
             print!("{} ", result);
         }
         println!("]")
     }
 
+    #[test]
+    fn test_manager_bubble_reuses_ggsws() {
+        // MAX and MIN read the same pair, so only the MAX of each swap bootstraps
+        let values = [9_usize, 16, 0, 4];
+        let size = values.len();
+        let mut manager = OperationManager::new(*SetI, size + 1, 8).unwrap();
+        manager
+            .add_operatoins(BubbleProgram::load_operatonis())
+            .unwrap();
+        manager
+            .set_execution_plan(BubbleProgram::load_programs(size))
+            .unwrap();
+        for (i, &value) in values.iter().enumerate() {
+            manager.load_data(value, i).unwrap();
+        }
+        let loaded = manager.lwe_lists.clone();
+        let swaps = size * (size - 1) / 2;
+
+        let stats = manager.execute().unwrap();
+        assert_eq!(stats.bootstrapped_slots, 2 * swaps);
+        let sorted: Vec<_> = (0..size).map(|i| manager.get_data(i).unwrap()).collect();
+        assert_eq!(sorted, vec![0, 4, 9, 16]);
+
+        let sequential = std::mem::replace(&mut manager.lwe_lists, loaded.clone());
+        let stats = manager.execute_parallel().unwrap();
+        assert_eq!(stats.bootstrapped_slots, 2 * swaps);
+        assert!(manager.lwe_lists == sequential);
+
+        // slot 3 gets the GGSWs of slot 0 through the MOVE, the MIN bootstraps nothing
+        manager
+            .set_execution_plan(vec![
+                Step::new(0, vec![0, 1], 4),
+                Step::new(2, vec![0], 3),
+                Step::new(1, vec![3, 1], 2),
+            ])
+            .unwrap();
+        manager.lwe_lists = loaded.clone();
+        assert_eq!(manager.execute().unwrap().bootstrapped_slots, 2);
+        assert_eq!(manager.get_data(2).unwrap(), 9);
+        manager.lwe_lists = loaded;
+        assert_eq!(manager.execute_parallel().unwrap().bootstrapped_slots, 2);
+        assert_eq!(manager.get_data(2).unwrap(), 9);
+    }
+
+    #[test]
+    fn test_manager_bubble_fused() {
+        let values = [9_usize, 16, 0, 4];
+        let size = values.len();
+        let mut manager = OperationManager::new(*SetI, size, 8).unwrap();
+        manager
+            .add_operatoins(BubbleProgram::load_operatonis())
+            .unwrap();
+        manager
+            .set_execution_plan(BubbleProgram::load_fused_programs(size))
+            .unwrap();
+        for (i, &value) in values.iter().enumerate() {
+            manager.load_data(value, i).unwrap();
+        }
+        manager.execute().unwrap();
+
+        let sorted: Vec<_> = (0..size).map(|i| manager.get_data(i).unwrap()).collect();
+        assert_eq!(sorted, vec![0, 4, 9, 16]);
+        assert_eq!(
+            manager.validate_plan(&[Step::new(1, vec![0, 1], 0).with_output(0, 0)]),
+            Err(ProcessorError::InvalidPlan(vec![PlanIssue {
+                step: 0,
+                error: ProcessorError::DuplicateOutput(0),
+            }]))
+        );
+    }
+
+    #[test]
+    fn test_manager_csel() {
+        let mut rng = rand::thread_rng();
+        for data_len in [8, 16, 32] {
+            let mut manager = OperationManager::new(*SetI, 6, data_len).unwrap();
+            manager
+                .add_operation(ArithmeticOp::CSEL, OperandType::BothCipher, None)
+                .unwrap();
+            // slots 3 and 4 hold a copy of a and b, the last two steps write over `a` and `b`
+            manager
+                .set_execution_plan(vec![
+                    Step::new(0, vec![0, 1, 2], 5),
+                    Step::new(0, vec![0, 1, 2], 1),
+                    Step::new(0, vec![0, 4, 3], 3),
+                ])
+                .unwrap();
+
+            for condition in [0, 1, 2, 0x80] {
+                let a = rng.gen_range(0..1_usize << data_len);
+                let b = rng.gen_range(0..1_usize << data_len);
+                manager.load_data(condition, 0).unwrap();
+                for (slot, value) in [(1, a), (2, b), (3, a), (4, b)] {
+                    manager.load_data(value, slot).unwrap();
+                }
+                manager.execute().unwrap();
+
+                let (selected, other) = if condition != 0 { (a, b) } else { (b, a) };
+                for (slot, expected) in [(5, selected), (1, selected), (3, other)] {
+                    assert_eq!(
+                        manager.get_data(slot).unwrap(),
+                        expected,
+                        "{data_len} bits, condition {condition}, slot {slot}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_manager_predicated_maximum() {
+        // buf[size] = buf[0]; for i in 1..size { if buf[i] > buf[size] { buf[size] = buf[i] } }
+        let values = [5_usize, 16, 4, 9];
+        let size = values.len();
+        let (maximum, condition, scratch) = (size, size + 1, size + 2);
+        let mut manager = OperationManager::new(*SetI, size + 3, 8).unwrap();
+        manager
+            .add_operatoins(vec![
+                (ArithmeticOp::GT, OperandType::BothCipher, None),
+                (ArithmeticOp::MOVE, OperandType::BothCipher, None),
+            ])
+            .unwrap();
+        let element = Slot::at(0, 1);
+        let nodes = vec![
+            Step::new(1, vec![0], maximum).into(),
+            PlanNode::Repeat {
+                count: size - 1,
+                body: vec![
+                    PlanNode::Step(StepTemplate::new(
+                        0,
+                        vec![element, Slot::Fixed(maximum)],
+                        Slot::Fixed(condition),
+                    )),
+                    PlanNode::Predicated {
+                        condition: Slot::Fixed(condition),
+                        scratch: Slot::Fixed(scratch),
+                        body: vec![PlanNode::Step(StepTemplate::new(
+                            1,
+                            vec![element],
+                            Slot::Fixed(maximum),
+                        ))],
+                    },
+                ],
+            },
+        ];
+        manager.set_structured_plan(&nodes).unwrap();
+        // GT, MOVE into the scratch slot and CSEL per element
+        assert_eq!(manager.execution_plan.len(), 1 + 3 * (size - 1));
+        assert_eq!(manager.operations.len(), 3);
+
+        for (i, &value) in values.iter().enumerate() {
+            manager.load_data(value, i).unwrap();
+        }
+        manager.execute().unwrap();
+        assert_eq!(manager.get_data(maximum).unwrap(), 16);
+    }
+
     #[test]
     fn test_manager_squaresum() {
         let size = 5_usize;
-        let mut manager = OperationManager::new(*SetI, size + 2, 8);
-        manager.add_operatoins(SquaresumProgram::load_operatonis());
-        manager.set_execution_plan(SquaresumProgram::load_programs(size));
+        let mut manager = OperationManager::new(*SetI, size + 2, 8).unwrap();
+        manager
+            .add_operatoins(SquaresumProgram::load_operatonis())
+            .unwrap();
+        manager
+            .set_execution_plan(SquaresumProgram::load_programs(size))
+            .unwrap();
 
-        manager.load_data(2, 0);
-        manager.load_data(4, 1);
-        manager.load_data(0, 2);
-        manager.load_data(9, 3);
-        manager.load_data(5, 4);
+        manager.load_data(2, 0).unwrap();
+        manager.load_data(4, 1).unwrap();
+        manager.load_data(0, 2).unwrap();
+        manager.load_data(9, 3).unwrap();
+        manager.load_data(5, 4).unwrap();
 
         let t = Instant::now();
         for _ in 0..SAMPLE_SIZE {
-            manager.load_data(0, size + 1); // buf[size] = 0
-            manager.execute();
+            manager.load_data(0, size + 1).unwrap(); // buf[size] = 0
+            manager.execute().unwrap();
         }
 
         println!(
             "Execution time: {:.3?}",
             t.elapsed() / SAMPLE_SIZE.cast_into()
         );
-        let result = manager.get_data(size + 1);
+        let result = manager.get_data(size + 1).unwrap();
         println!(
             "Square sum of [2,4,0,9,5] is {}, which should be {}",
             result,
@@ -112,30 +374,83 @@ mod manager_tests {
         );
     }
 
+    #[test]
+    fn test_dependency_levels() {
+        let plan = vec![
+            Step::new(0, vec![0, 1], 4),
+            Step::new(0, vec![2, 3], 5),
+            Step::new(0, vec![4, 5], 0),
+            Step::new(1, vec![1], 1), // only overwrites a slot read by step 0
+            Step::new(0, vec![0, 1], 2),
+        ];
+        assert_eq!(
+            dependency_levels(&plan),
+            vec![vec![0, 1, 3], vec![2], vec![4]]
+        );
+    }
+
+    #[test]
+    fn test_manager_parallel() {
+        let values = [3_usize, 1, 4, 2];
+        let mut prog = ProgramBuilder::new();
+        let inputs: Vec<_> = values.iter().map(|_| prog.input()).collect();
+        let squares: Vec<_> = inputs.iter().map(|&x| prog.mul(x, x)).collect();
+        let left = prog.add(squares[0], squares[1]);
+        let right = prog.add(squares[2], squares[3]);
+        let sum = prog.add(left, right);
+        prog.output(sum);
+        let program = prog.build();
+        assert_eq!(dependency_levels(&program.steps).len(), 3);
+
+        let mut manager = OperationManager::new(*SetI, program.memory_size, 8).unwrap();
+        manager.add_operatoins(program.operations).unwrap();
+        manager.set_execution_plan(program.steps).unwrap();
+        for (&value, &slot) in values.iter().zip(&program.input_slots) {
+            manager.load_data(value, slot).unwrap();
+        }
+        let loaded = manager.lwe_lists.clone();
+
+        let t = Instant::now();
+        manager.execute().unwrap();
+        println!("Sequential execution time: {:.3?}", t.elapsed());
+        let sequential = std::mem::replace(&mut manager.lwe_lists, loaded);
+
+        let t = Instant::now();
+        manager.execute_parallel().unwrap();
+        println!("Parallel execution time: {:.3?}", t.elapsed());
+
+        assert!(manager.lwe_lists == sequential);
+        assert_eq!(manager.get_data(program.output_slots[0]).unwrap(), 30);
+    }
+
     #[test]
     fn test_manager_average() {
         let size = 5_usize;
-        let mut manager = OperationManager::new(*SetI, size + 1, 8);
-        manager.add_operatoins(AverageProgram::load_operatonis(size));
-        manager.set_execution_plan(AverageProgram::load_programs(size));
+        let mut manager = OperationManager::new(*SetI, size + 1, 8).unwrap();
+        manager
+            .add_operatoins(AverageProgram::load_operatonis(size))
+            .unwrap();
+        manager
+            .set_execution_plan(AverageProgram::load_programs(size))
+            .unwrap();
 
-        manager.load_data(2, 0);
-        manager.load_data(4, 1);
-        manager.load_data(0, 2);
-        manager.load_data(9, 3);
-        manager.load_data(5, 4);
+        manager.load_data(2, 0).unwrap();
+        manager.load_data(4, 1).unwrap();
+        manager.load_data(0, 2).unwrap();
+        manager.load_data(9, 3).unwrap();
+        manager.load_data(5, 4).unwrap();
 
         let t = Instant::now();
         for _ in 0..SAMPLE_SIZE {
-            manager.load_data(0, size);
-            manager.execute();
+            manager.load_data(0, size).unwrap();
+            manager.execute().unwrap();
         }
 
         println!(
             "Execution time: {:.3?}",
             t.elapsed() / SAMPLE_SIZE.cast_into()
         );
-        let result = manager.get_data(size);
+        let result = manager.get_data(size).unwrap();
         println!(
             "Average of [2,4,0,9,5] is {}, which should be {}",
             result,
@@ -146,9 +461,13 @@ mod manager_tests {
     #[test]
     fn test_manager_large_compare_cc() {
         let size = 2_usize;
-        let mut manager = OperationManager::new(*SetII, size + 1, 16);
-        manager.add_operation(ArithmeticOp::LT, OperandType::BothCipher, None);
-        manager.set_execution_plan(vec![Step::new(0, vec![0, 1], size)]);
+        let mut manager = OperationManager::new(*SetII, size + 1, 16).unwrap();
+        manager
+            .add_operation(ArithmeticOp::LT, OperandType::BothCipher, None)
+            .unwrap();
+        manager
+            .set_execution_plan(vec![Step::new(0, vec![0, 1], size)])
+            .unwrap();
         let mut rng = rand::thread_rng();
         let mut count = 0;
         let t = Instant::now();
@@ -158,10 +477,10 @@ mod manager_tests {
             let a = a % 65536;
             let b = b % 65536;
             let true_result: usize = if a < b { 1 } else { 0 };
-            manager.load_data(a.cast_into(), 0);
-            manager.load_data(b.cast_into(), 1);
-            manager.execute();
-            let result = manager.get_data(size);
+            manager.load_data(a.cast_into(), 0).unwrap();
+            manager.load_data(b.cast_into(), 1).unwrap();
+            manager.execute().unwrap();
+            let result = manager.get_data(size).unwrap();
             println!(
                 " 16-bit-CC-LT({}, {}) = {}, which should be {}",
                 a, b, result, true_result
@@ -185,9 +504,13 @@ mod manager_tests {
     fn test_manager_large_compare_pc() {
         let size = 1_usize;
         let b = 32718_usize;
-        let mut manager = OperationManager::new(*SetII, size + 1, 16);
-        manager.add_operation(ArithmeticOp::GT, OperandType::CipherPlain, Some(b));
-        manager.set_execution_plan(vec![Step::new(0, vec![0], size)]);
+        let mut manager = OperationManager::new(*SetII, size + 1, 16).unwrap();
+        manager
+            .add_operation(ArithmeticOp::GT, OperandType::CipherPlain, Some(b))
+            .unwrap();
+        manager
+            .set_execution_plan(vec![Step::new(0, vec![0], size)])
+            .unwrap();
         let mut rng = rand::thread_rng();
         let mut count = 0;
         let t = Instant::now();
@@ -195,9 +518,9 @@ mod manager_tests {
             let a: u32 = rng.gen();
             let a = a % 65536;
             let true_result: usize = if a > b.cast_into() { 1 } else { 0 };
-            manager.load_data(a.cast_into(), 0);
-            manager.execute();
-            let result = manager.get_data(size);
+            manager.load_data(a.cast_into(), 0).unwrap();
+            manager.execute().unwrap();
+            let result = manager.get_data(size).unwrap();
 
             println!(
                 "16-bit-CP-GT({}, {}) = {}, which should be {}",
@@ -217,13 +540,133 @@ mod manager_tests {
         );
     }
 
+    #[test]
+    fn test_manager_signed() {
+        let size = 2_usize;
+        let ops = [
+            (ArithmeticOp::LT, OperandType::BothCipher, None),
+            (ArithmeticOp::SR, OperandType::CipherPlain, Some(2)),
+            (ArithmeticOp::Div, OperandType::BothCipher, None),
+            (ArithmeticOp::Mod, OperandType::BothCipher, None),
+            (ArithmeticOp::MIN, OperandType::BothCipher, None),
+            (ArithmeticOp::MAX, OperandType::BothCipher, None),
+        ];
+        let mut manager = OperationManager::new(*SetI, size + ops.len(), 8).unwrap();
+        for (op, op_type, immediate) in ops {
+            manager
+                .add_operation_with_signedness(op, op_type, Signedness::Signed, immediate)
+                .unwrap();
+        }
+        manager
+            .set_execution_plan(vec![
+                Step::new(0, vec![0, 1], size),
+                Step::new(1, vec![0], size + 1),
+                Step::new(2, vec![0, 1], size + 2),
+                Step::new(3, vec![0, 1], size + 3),
+                Step::new(4, vec![0, 1], size + 4),
+                Step::new(5, vec![0, 1], size + 5),
+            ])
+            .unwrap();
+        let mut rng = rand::thread_rng();
+        // the edge pairs cover MIN / -1, a division by zero and mixed signs
+        let mut pairs: Vec<(i8, i8)> = vec![
+            (-128, -1),
+            (-128, 1),
+            (127, -128),
+            (-7, 2),
+            (7, -2),
+            (-1, 0),
+        ];
+        pairs.extend((0..SAMPLE_SIZE).map(|_| (rng.gen(), rng.gen())));
+        let t = Instant::now();
+        for &(a, b) in pairs.iter() {
+            let (div, rem) = if b == 0 {
+                (0, 0)
+            } else {
+                (a.wrapping_div(b), a.wrapping_rem(b))
+            };
+            let expected = [(a < b) as i8, a >> 2, div, rem, a.min(b), a.max(b)];
+            manager.load_data(a as u8 as usize, 0).unwrap();
+            manager.load_data(b as u8 as usize, 1).unwrap();
+            manager.execute().unwrap();
+            for (i, &value) in expected.iter().enumerate() {
+                let result = manager.get_data(size + i).unwrap() as u8 as i8;
+                println!(
+                    "signed {:?}({}, {}) = {}, which should be {}",
+                    ops[i].0, a, b, result, value
+                );
+                assert_eq!(result, value);
+            }
+        }
+        println!(
+            "Execution time: {:.3?}",
+            t.elapsed() / pairs.len().cast_into()
+        );
+    }
+
+    #[test]
+    fn test_manager_signed_large_compare() {
+        let size = 2_usize;
+        let mut rng = rand::thread_rng();
+        for bits in [16_usize, 32] {
+            let min = -(1_i64 << (bits - 1));
+            let max = (1_i64 << (bits - 1)) - 1;
+            let mask = (1_u64 << bits) - 1;
+            let b = -1000_i64;
+            let immediate = (b as u64 & mask) as usize;
+            let mut manager = OperationManager::new(*SetII, size + 2, bits).unwrap();
+            for (op, op_type, immediate) in [
+                (ArithmeticOp::GT, OperandType::BothCipher, None),
+                (ArithmeticOp::LT, OperandType::CipherPlain, Some(immediate)),
+            ] {
+                manager
+                    .add_operation_with_signedness(op, op_type, Signedness::Signed, immediate)
+                    .unwrap();
+            }
+            manager
+                .set_execution_plan(vec![
+                    Step::new(0, vec![0, 1], size),
+                    Step::new(1, vec![0], size + 1),
+                ])
+                .unwrap();
+            let mut pairs = vec![
+                (min, max),
+                (max, min),
+                (-1, 0),
+                (0, -1),
+                (-1, -1),
+                (b, b + 1),
+            ];
+            pairs.extend(
+                (0..SAMPLE_SIZE).map(|_| (rng.gen_range(min..=max), rng.gen_range(min..=max))),
+            );
+            for &(x, y) in pairs.iter() {
+                manager.load_data((x as u64 & mask) as usize, 0).unwrap();
+                manager.load_data((y as u64 & mask) as usize, 1).unwrap();
+                manager.execute().unwrap();
+                let gt = manager.get_data(size).unwrap();
+                let lt = manager.get_data(size + 1).unwrap();
+                println!(
+                    "{}-bit signed GT({}, {}) = {}, LT({}, {}) = {}",
+                    bits, x, y, gt, x, b, lt
+                );
+                assert_eq!(gt, (x > y) as usize);
+                assert_eq!(lt, (x < b) as usize);
+            }
+        }
+    }
+
     #[test]
     fn test_manager_sign() {
         let size = 1_usize;
         let b = 1 << 31;
-        let mut manager = OperationManager::new(*SetII, size + 1, 32);
-        manager.add_operation(ArithmeticOp::SIGN, OperandType::CipherPlain, Some(b));
-        manager.set_execution_plan(vec![Step::new(0, vec![0], size)]);
+        let mut manager = OperationManager::new(*SetII, size + 1, 32).unwrap();
+        manager
+            .add_operation(ArithmeticOp::SIGN, OperandType::CipherPlain, Some(b))
+            .unwrap();
+        manager
+            .set_execution_plan(vec![Step::new(0, vec![0], size)])
+            .unwrap();
         let mut rng = rand::thread_rng();
         let mut count = 0;
         let t = Instant::now();
@@ -237,9 +680,9 @@ mod manager_tests {
             } else {
                 (1 << 32) - 1
             };
-            manager.load_data(a.cast_into(), 0);
-            manager.execute();
-            let result = manager.get_data(size);
+            manager.load_data(a.cast_into(), 0).unwrap();
+            manager.execute().unwrap();
+            let result = manager.get_data(size).unwrap();
 
             println!(
                 " sign({}, {}) = {}, which should be {}",
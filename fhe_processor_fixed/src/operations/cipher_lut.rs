@@ -10,7 +10,8 @@ use tfhe::core_crypto::{
 use tfhe::{
     boolean::prelude::PolynomialSize,
     core_crypto::prelude::{
-        ComputationBuffers, Fft, FourierGgswCiphertextList, LweCiphertext, PolynomialList,
+        CastFrom, ComputationBuffers, Fft, FourierGgswCiphertextList, LweCiphertext,
+        PolynomialList, UnsignedTorus,
     },
 };
 /// Pack multiple lookup tables into encrypted lookup tables 
@@ -24,7 +25,7 @@ use tfhe::{
 /// - `delta`: Scaling factor (used in TFHE encoding).
 ///
 /// # Returns
-/// - `Vec<PolynomialList<Vec<u64>>>`: A vector of encrypted lookup tables, 
+/// - `Vec<PolynomialList<Vec<Scalar>>>`: A vector of encrypted lookup tables, 
 ///   where each table corresponds to one `PolynomialList`.
 ///
 /// # Example
@@ -32,26 +33,26 @@ use tfhe::{
 /// let lut_lists = generate_lut_from_vecs(&split_tables, PolynomialSize(1024), 1 << 40);
 /// // `lut_lists[i]` is the encrypted lookup table (PolynomialList) for the i-th table.
 /// ```
-pub fn generate_lut_from_vecs(
+pub fn generate_lut_from_vecs<Scalar: UnsignedTorus + CastFrom<usize>>(
     tables: &[Vec<usize>],
     polynomial_size: PolynomialSize,
-    delta: u64,
-) -> Vec<PolynomialList<Vec<u64>>> {
+    delta: Scalar,
+) -> Vec<PolynomialList<Vec<Scalar>>> {
     let mut result = Vec::with_capacity(tables.len());
 
     for (_, table) in tables.iter().enumerate() {
         let table_len = table.len();
         let num_poly = (table_len + polynomial_size.0 - 1) / polynomial_size.0;
 
-        let mut flat: Vec<u64> = Vec::with_capacity(num_poly * polynomial_size.0);
+        let mut flat: Vec<Scalar> = Vec::with_capacity(num_poly * polynomial_size.0);
 
         for poly_idx in 0..num_poly {
             for i in 0..polynomial_size.0 {
                 let idx = poly_idx * polynomial_size.0 + i;
                 let val = if idx < table_len {
-                    (table[idx] as u64) * delta
+                    Scalar::cast_from(table[idx]) * delta
                 } else {
-                    0
+                    Scalar::ZERO
                 };
                 flat.push(val);
             }
@@ -63,11 +64,11 @@ pub fn generate_lut_from_vecs(
     result
 }
 
-pub fn generate_lut_from_vecs_auto(
+pub fn generate_lut_from_vecs_auto<Scalar: UnsignedTorus + CastFrom<usize>>(
     tables: &[Vec<usize>],
     polynomial_size: PolynomialSize,
-    delta: u64,
-) -> (Vec<PolynomialList<Vec<u64>>>, usize) {
+    delta: Scalar,
+) -> (Vec<PolynomialList<Vec<Scalar>>>, usize) {
     assert!(!tables.is_empty(), "tables must not be empty");
 
     let table_len = tables[0].len();
@@ -91,15 +92,15 @@ pub fn generate_lut_from_vecs_auto(
             let table_len = table.len();
             let num_poly = (table_len + n - 1) / n;
 
-            let mut flat: Vec<u64> = Vec::with_capacity(num_poly * n);
+            let mut flat: Vec<Scalar> = Vec::with_capacity(num_poly * n);
 
             for poly_idx in 0..num_poly {
                 for i in 0..n {
                     let idx = poly_idx * n + i;
                     let val = if idx < table_len {
-                        (table[idx] as u64) * delta
+                        Scalar::cast_from(table[idx]) * delta
                     } else {
-                        0
+                        Scalar::ZERO
                     };
                     flat.push(val);
                 }
@@ -119,7 +120,7 @@ pub fn generate_lut_from_vecs_auto(
     let mut result = Vec::with_capacity(num_groups);
 
     for g in 0..num_groups {
-        let mut flat = vec![0u64; n];
+        let mut flat = vec![Scalar::ZERO; n];
 
         for s in 0..per_poly_capacity {
             let table_idx = g * per_poly_capacity + s;
@@ -132,7 +133,7 @@ pub fn generate_lut_from_vecs_auto(
             let table = &tables[table_idx];
 
             for j in 0..table_len {
-                flat[slot_base + j] = (table[j] as u64) * delta;
+                flat[slot_base + j] = Scalar::cast_from(table[j]) * delta;
             }
         }
 
@@ -158,9 +159,9 @@ pub fn generate_lut_from_vecs_auto(
 /// # Description
 /// Performs a complete lookup using vertical_packing. 
 /// The result is written into `lwe_out`.
-pub fn tfhe_vertical_packing_lookup(
-    lut: &PolynomialList<Vec<u64>>,
-    lwe_out: &mut LweCiphertext<Vec<u64>>,
+pub fn tfhe_vertical_packing_lookup<Scalar: UnsignedTorus>(
+    lut: &PolynomialList<Vec<Scalar>>,
+    lwe_out: &mut LweCiphertext<Vec<Scalar>>,
     ggsw_list: &FourierGgswCiphertextList<Vec<c64>>,
     fft: &Fft,
     buffer: &mut ComputationBuffers,
@@ -169,7 +170,7 @@ pub fn tfhe_vertical_packing_lookup(
     let _ = lut_input_size;
     // 确保buffer大小足够
     buffer.resize(
-        vertical_packing_scratch::<u64>(
+        vertical_packing_scratch::<Scalar>(
             ggsw_list.glwe_size(),
             ggsw_list.polynomial_size(),
             lut.polynomial_count(),
@@ -196,9 +197,9 @@ pub fn tfhe_vertical_packing_lookup(
 /// - `luts`: Array of lookup tables (each a `PolynomialList`)
 /// - `lwe_outs`: Array of output ciphertexts (each an `LweCiphertext`)
 /// - Others: Same as above
-pub fn tfhe_vertical_packing_multi_lookup(
-    luts: &[PolynomialList<Vec<u64>>],
-    lwe_outs: &mut [LweCiphertext<Vec<u64>>],
+pub fn tfhe_vertical_packing_multi_lookup<Scalar: UnsignedTorus>(
+    luts: &[PolynomialList<Vec<Scalar>>],
+    lwe_outs: &mut [LweCiphertext<Vec<Scalar>>],
     ggsw_list: &FourierGgswCiphertextList<Vec<c64>>,
     fft: &Fft,
     buffer: &mut ComputationBuffers,
@@ -0,0 +1,372 @@
+use crate::{error::ProcessorError, operations::manager::Step};
+
+/// A memory slot of a [`StepTemplate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    Fixed(usize),
+    /// `base + stride * i`, `i` being the index of the enclosing [`PlanNode::Repeat`] at
+    /// nesting `level`, 0 for the outermost one.
+    Indexed {
+        level: usize,
+        base: usize,
+        stride: usize,
+    },
+}
+
+impl Slot {
+    /// `base + i` for the index `i` of the repeat at nesting `level`.
+    pub fn at(level: usize, base: usize) -> Self {
+        Slot::Indexed {
+            level,
+            base,
+            stride: 1,
+        }
+    }
+
+    fn resolve(&self, indices: &[usize]) -> Result<usize, ProcessorError> {
+        match *self {
+            Slot::Fixed(slot) => Ok(slot),
+            Slot::Indexed {
+                level,
+                base,
+                stride,
+            } => indices.get(level).map(|index| base + stride * index).ok_or(
+                ProcessorError::UnboundLoopIndex {
+                    level,
+                    depth: indices.len(),
+                },
+            ),
+        }
+    }
+}
+
+impl From<usize> for Slot {
+    fn from(slot: usize) -> Self {
+        Slot::Fixed(slot)
+    }
+}
+
+/// A [`Step`] over [`Slot`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepTemplate {
+    pub op_index: usize,
+    pub input_indices: Vec<Slot>,
+    pub output_index: Slot,
+    pub fused: Vec<(usize, Slot)>,
+}
+
+impl StepTemplate {
+    pub fn new(op_index: usize, input_indices: Vec<Slot>, output_index: Slot) -> Self {
+        Self {
+            op_index,
+            input_indices,
+            output_index,
+            fused: Vec::new(),
+        }
+    }
+
+    /// See [`Step::with_output`].
+    pub fn with_output(mut self, op_index: usize, output_index: Slot) -> Self {
+        self.fused.push((op_index, output_index));
+        self
+    }
+
+    fn resolve(&self, indices: &[usize]) -> Result<Step, ProcessorError> {
+        let input_indices = self
+            .input_indices
+            .iter()
+            .map(|slot| slot.resolve(indices))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut step = Step::new(
+            self.op_index,
+            input_indices,
+            self.output_index.resolve(indices)?,
+        );
+        for (op_index, output_index) in &self.fused {
+            step = step.with_output(*op_index, output_index.resolve(indices)?);
+        }
+        Ok(step)
+    }
+}
+
+impl From<Step> for StepTemplate {
+    fn from(step: Step) -> Self {
+        Self {
+            op_index: step.op_index,
+            input_indices: step.input_indices.into_iter().map(Slot::Fixed).collect(),
+            output_index: Slot::Fixed(step.output_index),
+            fused: step
+                .fused
+                .into_iter()
+                .map(|(op_index, output_index)| (op_index, Slot::Fixed(output_index)))
+                .collect(),
+        }
+    }
+}
+
+/// A structured execution plan, flattened into steps by [`expand_plan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanNode {
+    Step(StepTemplate),
+    /// `body` run `count` times, its [`Slot::Indexed`] slots following the index.
+    Repeat {
+        count: usize,
+        body: Vec<PlanNode>,
+    },
+    /// `body` run unconditionally, its writes only taking effect where the encrypted
+    /// `condition` is non-zero.
+    ///
+    /// Each step writes into `scratch` instead (`scratch + k` for the `k`-th output of a fused
+    /// step), then a CSEL copies the scratch slot into the real output if the condition holds.
+    /// The body must not write the condition, nor read or write the scratch slots.
+    Predicated {
+        condition: Slot,
+        scratch: Slot,
+        body: Vec<PlanNode>,
+    },
+}
+
+impl PlanNode {
+    /// Whether the node holds a predicated block, i.e. needs a CSEL operation.
+    pub fn has_predicated(&self) -> bool {
+        match self {
+            PlanNode::Step(_) => false,
+            PlanNode::Repeat { body, .. } => body.iter().any(PlanNode::has_predicated),
+            PlanNode::Predicated { .. } => true,
+        }
+    }
+}
+
+impl From<Step> for PlanNode {
+    fn from(step: Step) -> Self {
+        PlanNode::Step(step.into())
+    }
+}
+
+/// Flatten `nodes` into straight-line steps, unrolling the repeats and turning the writes of
+/// the predicated blocks into CSELs run by operation `csel_op_index`.
+///
+/// # Errors
+///
+/// [`ProcessorError::UnboundLoopIndex`] for a slot indexed by a repeat that does not enclose
+/// it, [`ProcessorError::ConditionWritten`] for a predicated block writing its condition and
+/// [`ProcessorError::ScratchOverlap`] for one using its scratch slots in the body.
+pub fn expand_plan(nodes: &[PlanNode], csel_op_index: usize) -> Result<Vec<Step>, ProcessorError> {
+    let mut steps = Vec::new();
+    expand_into(nodes, csel_op_index, &mut Vec::new(), &mut steps)?;
+    Ok(steps)
+}
+
+fn expand_into(
+    nodes: &[PlanNode],
+    csel_op_index: usize,
+    indices: &mut Vec<usize>,
+    steps: &mut Vec<Step>,
+) -> Result<(), ProcessorError> {
+    for node in nodes {
+        match node {
+            PlanNode::Step(template) => steps.push(template.resolve(indices)?),
+            PlanNode::Repeat { count, body } => {
+                for index in 0..*count {
+                    indices.push(index);
+                    let expanded = expand_into(body, csel_op_index, indices, steps);
+                    indices.pop();
+                    expanded?;
+                }
+            }
+            PlanNode::Predicated {
+                condition,
+                scratch,
+                body,
+            } => {
+                let condition = condition.resolve(indices)?;
+                let scratch = scratch.resolve(indices)?;
+                let mut block = Vec::new();
+                expand_into(body, csel_op_index, indices, &mut block)?;
+                check_scratch(&block, scratch)?;
+                for step in block {
+                    predicate(&step, condition, scratch, csel_op_index, steps)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Make sure no step of `block` reads or writes the scratch slots, otherwise a CSEL would copy
+/// a slot onto itself or a later step would read a scratch value.
+fn check_scratch(block: &[Step], scratch: usize) -> Result<(), ProcessorError> {
+    let width = block.iter().map(|step| step.outputs().count()).max();
+    let scratch_slots = scratch..scratch + width.unwrap_or(0);
+    for step in block {
+        let outputs = step.outputs().map(|(_, output_index)| output_index);
+        let mut slots = step.input_indices.iter().copied().chain(outputs);
+        if let Some(slot) = slots.find(|slot| scratch_slots.contains(slot)) {
+            return Err(ProcessorError::ScratchOverlap(slot));
+        }
+    }
+    Ok(())
+}
+
+/// Push `step` writing into the scratch slots, then one CSEL per output.
+fn predicate(
+    step: &Step,
+    condition: usize,
+    scratch: usize,
+    csel_op_index: usize,
+    steps: &mut Vec<Step>,
+) -> Result<(), ProcessorError> {
+    let outputs: Vec<(usize, usize)> = step.outputs().collect();
+    for (k, &(_, output_index)) in outputs.iter().enumerate() {
+        if output_index == condition || scratch + k == condition {
+            return Err(ProcessorError::ConditionWritten(condition));
+        }
+    }
+
+    let mut guarded = Step::new(step.op_index, step.input_indices.clone(), scratch);
+    for (k, &(op_index, _)) in outputs.iter().enumerate().skip(1) {
+        guarded = guarded.with_output(op_index, scratch + k);
+    }
+    steps.push(guarded);
+    for (k, &(_, output_index)) in outputs.iter().enumerate() {
+        // output = condition ? scratch : output
+        steps.push(Step::new(
+            csel_op_index,
+            vec![condition, scratch + k, output_index],
+            output_index,
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::programs::{bubble::BubbleProgram, maximum::MaximumProgram};
+
+    #[test]
+    fn test_expand_repeat() {
+        let size = 5;
+        assert_eq!(
+            expand_plan(&MaximumProgram::load_repeat_programs(size), 0),
+            Ok(MaximumProgram::load_programs(size))
+        );
+
+        let steps = expand_plan(&BubbleProgram::load_repeat_programs(size), 0).unwrap();
+        assert_eq!(steps.len(), (size - 1) * (size - 1));
+        assert_eq!(steps[0], Step::new(1, vec![0, 1], 0).with_output(0, 1));
+        assert_eq!(
+            steps[size - 2],
+            Step::new(1, vec![3, 4], 3).with_output(0, 4)
+        );
+
+        let nodes = vec![PlanNode::Repeat {
+            count: 2,
+            body: vec![PlanNode::Repeat {
+                count: 3,
+                body: vec![PlanNode::Step(StepTemplate::new(
+                    0,
+                    vec![
+                        Slot::at(0, 0),
+                        Slot::Indexed {
+                            level: 1,
+                            base: 10,
+                            stride: 2,
+                        },
+                    ],
+                    Slot::Fixed(20),
+                ))],
+            }],
+        }];
+        let inputs: Vec<_> = expand_plan(&nodes, 0)
+            .unwrap()
+            .into_iter()
+            .map(|step| step.input_indices)
+            .collect();
+        assert_eq!(
+            inputs,
+            vec![
+                vec![0, 10],
+                vec![0, 12],
+                vec![0, 14],
+                vec![1, 10],
+                vec![1, 12],
+                vec![1, 14]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_predicated() {
+        let csel = 7;
+        let nodes = vec![PlanNode::Predicated {
+            condition: Slot::Fixed(9),
+            scratch: Slot::Fixed(10),
+            body: vec![
+                Step::new(0, vec![0, 1], 2).into(),
+                Step::new(1, vec![2, 3], 2).with_output(2, 3).into(),
+            ],
+        }];
+        assert_eq!(
+            expand_plan(&nodes, csel),
+            Ok(vec![
+                Step::new(0, vec![0, 1], 10),
+                Step::new(csel, vec![9, 10, 2], 2),
+                Step::new(1, vec![2, 3], 10).with_output(2, 11),
+                Step::new(csel, vec![9, 10, 2], 2),
+                Step::new(csel, vec![9, 11, 3], 3),
+            ])
+        );
+        assert!(nodes[0].has_predicated());
+    }
+
+    #[test]
+    fn test_expand_errors() {
+        let unbound = vec![PlanNode::Repeat {
+            count: 2,
+            body: vec![PlanNode::Step(StepTemplate::new(
+                0,
+                vec![Slot::at(1, 0)],
+                Slot::Fixed(0),
+            ))],
+        }];
+        assert_eq!(
+            expand_plan(&unbound, 0),
+            Err(ProcessorError::UnboundLoopIndex { level: 1, depth: 1 })
+        );
+
+        let overwritten = vec![PlanNode::Predicated {
+            condition: Slot::Fixed(3),
+            scratch: Slot::Fixed(4),
+            body: vec![Step::new(0, vec![0, 1], 3).into()],
+        }];
+        assert_eq!(
+            expand_plan(&overwritten, 0),
+            Err(ProcessorError::ConditionWritten(3))
+        );
+
+        // the fused step needs scratch slots 4 and 5, 5 being an output of the first step and
+        // read by the second one
+        let clobbered = vec![PlanNode::Predicated {
+            condition: Slot::Fixed(9),
+            scratch: Slot::Fixed(4),
+            body: vec![
+                Step::new(0, vec![0, 1], 5).into(),
+                Step::new(1, vec![5, 2], 2).with_output(2, 3).into(),
+            ],
+        }];
+        assert_eq!(
+            expand_plan(&clobbered, 0),
+            Err(ProcessorError::ScratchOverlap(5))
+        );
+        let written = vec![PlanNode::Predicated {
+            condition: Slot::Fixed(9),
+            scratch: Slot::Fixed(4),
+            body: vec![Step::new(0, vec![0, 1], 4).into()],
+        }];
+        assert_eq!(
+            expand_plan(&written, 0),
+            Err(ProcessorError::ScratchOverlap(4))
+        );
+    }
+}
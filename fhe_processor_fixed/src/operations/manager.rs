@@ -8,23 +8,26 @@ use tfhe::core_crypto::{
     prelude::{
         allocate_and_encrypt_new_lwe_ciphertext, allocate_and_generate_new_binary_glwe_secret_key,
         allocate_and_generate_new_lwe_bootstrap_key, convert_standard_lwe_bootstrap_key_to_fourier,
-        decrypt_lwe_ciphertext, encrypt_lwe_ciphertext, ActivatedRandomGenerator,
-        EncryptionRandomGenerator, Fft, FourierGgswCiphertextList, FourierLweBootstrapKey,
-        FourierLweBootstrapKeyOwned, GlweSecretKeyOwned, LweCiphertext, LweSecretKeyOwned,
-        Plaintext, SecretRandomGenerator,
+        decrypt_lwe_ciphertext, encrypt_lwe_ciphertext, ActivatedRandomGenerator, CastFrom,
+        CastInto, ComputationBuffers, EncryptionRandomGenerator, Fft, FourierGgswCiphertextList,
+        FourierLweBootstrapKey, FourierLweBootstrapKeyOwned, GlweSecretKeyOwned, LweCiphertext,
+        LweSecretKeyOwned, Plaintext, SecretRandomGenerator, UnsignedInteger, UnsignedTorus,
     },
     seeders::{new_seeder, Seeder},
 };
 
 use crate::{
+    error::{PlanIssue, ProcessorError},
     operations::{
-        operand::ArithmeticOp,
+        control::{expand_plan, PlanNode},
+        operand::{ArithmeticOp, Signedness},
         operation::{OperandType, Operation},
     },
     opmized_operations::{
         compare::{
             opmized_compare_bothcipher, opmized_compare_cipherplain, opmized_compare_plaincipher,
         },
+        csel::csel,
         sign::sign,
     },
     processors::{
@@ -37,10 +40,14 @@ use crate::{
     utils::parms::ProcessorParam,
 };
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Step {
     pub op_index: usize,           // Operation index in Vec
     pub input_indices: Vec<usize>, // Oparnd index(s) in Vec
     pub output_index: usize,       // result inedx in Vec
+    /// Further `(op_index, output_index)` pairs run on the same circuit bootstrapping of the
+    /// inputs, see [`Step::with_output`].
+    pub fused: Vec<(usize, usize)>,
 }
 
 impl Step {
@@ -49,33 +56,67 @@ impl Step {
             op_index,
             input_indices,
             output_index,
+            fused: Vec::new(),
         }
     }
+
+    /// Also write `op_index` applied to the same inputs into `output_index`, e.g. the MAX and
+    /// the MIN of a pair. Every output is written once all of them are computed, so they may
+    /// overwrite the inputs.
+    pub fn with_output(mut self, op_index: usize, output_index: usize) -> Self {
+        self.fused.push((op_index, output_index));
+        self
+    }
+
+    /// Every `(op_index, output_index)` pair of the step, the first one first.
+    pub fn outputs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        std::iter::once((self.op_index, self.output_index)).chain(self.fused.iter().copied())
+    }
+}
+
+/// What one run of [`OperationManager::execute`] or [`OperationManager::execute_parallel`] did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExecutionStats {
+    /// Memory slots circuit-bootstrapped, each read of a slot written since its last
+    /// bootstrapping counting once.
+    pub bootstrapped_slots: usize,
 }
 
-pub struct OperationManager {
-    pub operations: Vec<Operation>,
+pub struct OperationManager<Scalar: UnsignedTorus> {
+    pub operations: Vec<Operation<Scalar>>,
     pub execution_plan: Vec<Step>,
     pub fft: Fft,
     pub boxed_seeder: Box<dyn Seeder>,
     pub secret_generator: SecretRandomGenerator<ActivatedRandomGenerator>,
     pub encryption_generator: EncryptionRandomGenerator<ActivatedRandomGenerator>,
 
-    pub params: ProcessorParam<u64>,
-    pub glwe_sk: GlweSecretKeyOwned<u64>,
-    pub lwe_sk_after_ks: LweSecretKeyOwned<u64>,
-    pub ksk: LweStoredReusedKeyswitchKey<Vec<u64>>,
+    pub params: ProcessorParam<Scalar>,
+    pub glwe_sk: GlweSecretKeyOwned<Scalar>,
+    pub lwe_sk_after_ks: LweSecretKeyOwned<Scalar>,
+    pub ksk: LweStoredReusedKeyswitchKey<Vec<Scalar>>,
     pub fourier_bsk: FourierLweBootstrapKeyOwned,
     pub auto_keys: HashMap<usize, AutomorphKey<ABox<[c64]>>>,
     pub ss_key: FourierGgswCiphertextList<Vec<c64>>,
 
+    // zeroed GGSWs for the LWEs of one memory slot, cloned for each bootstrapped slot
     pub ggsw_lists: Vec<FourierGgswCiphertextList<Vec<c64>>>,
-    pub lwe_lists: Vec<Vec<LweCiphertext<Vec<u64>>>>,
+    pub lwe_lists: Vec<Vec<LweCiphertext<Vec<Scalar>>>>,
     pub data_len: usize,
 }
 
-impl OperationManager {
-    pub fn new(param: ProcessorParam<u64>, mem_size: usize, data_len: usize) -> Self {
+impl<Scalar> OperationManager<Scalar>
+where
+    Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + Send + Sync,
+{
+    pub fn new(
+        param: ProcessorParam<Scalar>,
+        mem_size: usize,
+        data_len: usize,
+    ) -> Result<Self, ProcessorError> {
+        // the operations only come in these widths, and the values are loaded from a usize
+        if !matches!(data_len, 8 | 16 | 32) {
+            return Err(ProcessorError::UnsupportedDataLength(data_len));
+        }
         let lwe_dimension = param.lwe_dimension();
         let lwe_modular_std_dev = param.lwe_modular_std_dev();
         let polynomial_size = param.polynomial_size();
@@ -174,11 +215,11 @@ impl OperationManager {
             cbs_base_log,
             cbs_level,
         );
-        let fourier_ggsw_lists = vec![fourier_ggsw_list; data_len.div_ceil(message_size) * 2];
+        let fourier_ggsw_lists = vec![fourier_ggsw_list; data_len.div_ceil(message_size)];
 
         let lwe = allocate_and_encrypt_new_lwe_ciphertext(
             &glwe_lwe_sk,
-            Plaintext(0),
+            Plaintext(Scalar::ZERO),
             lwe_modular_std_dev,
             ciphertext_modulus,
             &mut encryption_generator,
@@ -187,7 +228,7 @@ impl OperationManager {
         let lwe_list = vec![lwe; data_len.div_ceil(message_size)];
         let lwe_lists = vec![lwe_list; mem_size];
 
-        Self {
+        Ok(Self {
             params: param,
             operations: Vec::new(),
             execution_plan: Vec::new(),
@@ -204,7 +245,7 @@ impl OperationManager {
             ggsw_lists: fourier_ggsw_lists,
             lwe_lists,
             data_len,
-        }
+        })
     }
 
     pub fn add_operation(
@@ -212,293 +253,538 @@ impl OperationManager {
         op: ArithmeticOp,
         op_type: OperandType,
         immediate: Option<usize>,
-    ) {
+    ) -> Result<(), ProcessorError> {
+        self.add_operation_with_signedness(op, op_type, Signedness::Unsigned, immediate)
+    }
+
+    /// [`OperationManager::add_operation`] reading the operands and the result with
+    /// `signedness`, e.g. GT, Div or SR on two's complement data for [`Signedness::Signed`].
+    pub fn add_operation_with_signedness(
+        &mut self,
+        op: ArithmeticOp,
+        op_type: OperandType,
+        signedness: Signedness,
+        immediate: Option<usize>,
+    ) -> Result<(), ProcessorError> {
         let operand = Operation::new(
             op,
             op_type,
+            signedness,
             self.data_len,
             self.params.extract_size(),
             self.params.polynomial_size(),
-            1 << (u64::BITS as usize - self.params.message_size()),
+            Scalar::ONE << (Scalar::BITS - self.params.message_size()),
             immediate,
-        );
+        )?;
         self.operations.push(operand);
+        Ok(())
     }
 
-    pub fn add_operatoins(&mut self, ops: Vec<(ArithmeticOp, OperandType, Option<usize>)>) {
+    pub fn add_operatoins(
+        &mut self,
+        ops: Vec<(ArithmeticOp, OperandType, Option<usize>)>,
+    ) -> Result<(), ProcessorError> {
         for (op, op_type, immediate) in ops {
-            self.add_operation(op, op_type, immediate);
+            self.add_operation(op, op_type, immediate)?;
         }
+        Ok(())
     }
 
     pub fn remove_operation(&mut self, index: usize) {
         self.operations.remove(index);
     }
 
-    pub fn set_execution_plan(&mut self, plan: Vec<Step>) {
+    /// Set the plan run by [`OperationManager::execute`], after checking it with
+    /// [`OperationManager::validate_plan`].
+    pub fn set_execution_plan(&mut self, plan: Vec<Step>) -> Result<(), ProcessorError> {
+        self.validate_plan(&plan)?;
         self.execution_plan = plan;
+        Ok(())
+    }
+
+    /// Expand `nodes` with [`expand_plan`] and set the result as the plan.
+    ///
+    /// The CSELs of the predicated blocks run the first CSEL operation, which is registered if
+    /// there is none yet.
+    pub fn set_structured_plan(&mut self, nodes: &[PlanNode]) -> Result<(), ProcessorError> {
+        let registered = self
+            .operations
+            .iter()
+            .position(|op| op.op == ArithmeticOp::CSEL);
+        let csel_op_index = match registered {
+            Some(index) => index,
+            None if nodes.iter().any(PlanNode::has_predicated) => {
+                self.add_operation(ArithmeticOp::CSEL, OperandType::BothCipher, None)?;
+                self.operations.len() - 1
+            }
+            // no CSEL step is emitted
+            None => 0,
+        };
+        let plan = expand_plan(nodes, csel_op_index)?;
+        self.set_execution_plan(plan)
     }
 
-    pub fn load_data(&mut self, data: usize, index: usize) {
-        assert!(index < self.lwe_lists.len(), "Index out of bounds");
+    /// Check every step of `plan` against the registered operations and the memory, without
+    /// running anything.
+    ///
+    /// All the problems are reported at once, each with the index of its step, in a
+    /// [`ProcessorError::InvalidPlan`].
+    pub fn validate_plan(&self, plan: &[Step]) -> Result<(), ProcessorError> {
+        let mut issues = Vec::new();
+        for (index, step) in plan.iter().enumerate() {
+            for error in self.step_errors(step) {
+                issues.push(PlanIssue { step: index, error });
+            }
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(ProcessorError::InvalidPlan(issues))
+        }
+    }
 
-        // map_chunks_to_lwe return Vec<(&mut LweCiphertext<Vec<u64>>, u64)>
+    pub fn load_data(&mut self, data: usize, index: usize) -> Result<(), ProcessorError> {
+        // map_chunks_to_lwe return Vec<(&mut LweCiphertext<Vec<Scalar>>, u64)>
         let mapping = map_chunks_to_lwe_mut(
             &mut self.lwe_lists,
             index,
             data,
             self.data_len,
             self.params.message_size(),
-        );
+        )?;
 
         for (lwe, chunk) in mapping {
             encrypt_lwe_ciphertext(
                 &self.glwe_sk.as_lwe_secret_key(),
                 lwe,
-                Plaintext(chunk << (u64::BITS as usize - self.params.message_size())),
+                Plaintext(
+                    Scalar::cast_from(chunk as usize)
+                        << (Scalar::BITS - self.params.message_size()),
+                ),
                 self.params.glwe_modular_std_dev(),
                 &mut self.encryption_generator,
             );
         }
+        Ok(())
     }
 
-    pub fn get_data(&mut self, index: usize) -> usize {
-        let lwe_list = self.lwe_lists.get(index).expect("Index out of bounds");
-        let mut chunks = vec![0_u64; self.data_len.div_ceil(self.params.message_size())];
+    pub fn get_data(&mut self, index: usize) -> Result<usize, ProcessorError> {
+        let lwe_list = lwe_list(&self.lwe_lists, index)?;
+        let mut chunks = vec![0_usize; self.data_len.div_ceil(self.params.message_size())];
 
         for (lwe, chunk) in lwe_list.iter().zip(chunks.iter_mut()) {
             let plain = decrypt_lwe_ciphertext(&self.glwe_sk.as_lwe_secret_key(), lwe);
-            *chunk = (((plain.0 >> (u64::BITS as usize - self.params.message_size() - 1)) + 1)
-                >> 1)
-                % (1 << self.params.message_size());
+            let decoded: usize =
+                (((plain.0 >> (Scalar::BITS - self.params.message_size() - 1)) + Scalar::ONE) >> 1)
+                    .cast_into();
+            *chunk = decoded % (1 << self.params.message_size());
         }
 
         let mut result: usize = 0;
         for &chunk in &chunks {
-            result = (result << self.params.message_size()) | (chunk as usize);
+            result = (result << self.params.message_size()) | chunk;
         }
 
-        result
+        Ok(result)
     }
 
     /// execute the whole pipline
-    pub fn execute(&mut self) {
+    ///
+    /// The GGSWs of a memory slot are kept until the slot is written, so steps reading the
+    /// same slot only circuit-bootstrap it once. The cache lives for one run, as
+    /// [`OperationManager::lwe_lists`] may be changed between runs.
+    pub fn execute(&mut self) -> Result<ExecutionStats, ProcessorError> {
+        // operations may have been removed since the plan was set
+        self.validate_plan(&self.execution_plan)?;
+        let mut stats = ExecutionStats::default();
+        let mut cache = GgswCache::new();
+        let mut buffer = ComputationBuffers::new();
         for step in &self.execution_plan {
-            let op = &self.operations[step.op_index];
-            let is_both_cipher = match op.op_type {
-                OperandType::BothCipher => true,
-                _ => false,
-            };
-            match (op.op, op.bit_width) {
-                (ArithmeticOp::CSEL, _) => {}
-                (ArithmeticOp::SIGN, _) => {
-                    let fourier_bsk_view = self.fourier_bsk.as_view();
-                    let auto_keys = &self.auto_keys;
-                    let ss_key_view = self.ss_key.as_view();
-                    let ksk = &self.ksk;
-                    let params = &self.params;
-
-                    let lwe0 = self.lwe_lists[step.input_indices[0]].as_slice();
-                    let lwe1_opt = match op.op_type {
-                        OperandType::BothCipher => {
-                            Some(self.lwe_lists[step.input_indices[1]].as_slice())
-                        }
-                        _ => None,
-                    };
-
-                    let mut ggsw_lists = std::mem::take(&mut self.ggsw_lists);
-
-                    let total_lwe = lwe0.len() + lwe1_opt.map_or(0, |s| s.len());
-
-                    ggsw_lists
-                        .par_iter_mut()
-                        .enumerate()
-                        .take(total_lwe)
-                        .for_each(|(i, ggsw)| {
-                            let lwe = if i < lwe0.len() {
-                                &lwe0[i]
-                            } else {
-                                &lwe1_opt.unwrap()[i - lwe0.len()]
-                            };
-
-                            circuit_bootstrapping_4_bits_at_once_rev_tr(
-                                lwe,
-                                ggsw,
-                                fourier_bsk_view,
-                                auto_keys,
-                                ss_key_view,
-                                ksk,
-                                params,
-                            );
-                        });
-
-                    self.ggsw_lists = ggsw_lists;
-                    let temp = self.ggsw_lists.clone();
-                    let input = concat_ggsw_lists(temp, false);
-                    sign(
-                        &input,
-                        self.lwe_lists[step.output_index].as_mut_slice(),
-                        op.immediate.unwrap(),
-                        &self.fft,
-                    );
+            let context = StepContext::new(self);
+            for &slot in context.bootstrapped_inputs(step) {
+                if !cache.contains_key(&slot) {
+                    let ggsws = context.bootstrap_slot(slot)?;
+                    cache.insert(slot, ggsws);
+                    stats.bootstrapped_slots += 1;
                 }
-                (ArithmeticOp::MOVE, _) => {
-                    let temp = self.lwe_lists[step.input_indices[0]].clone();
-                    for (input, output) in temp
-                        .iter()
-                        .zip(self.lwe_lists[step.output_index].iter_mut())
-                    {
-                        output.clone_from(input);
-                    }
-                    continue;
+            }
+            let outputs = context.run_step(step, &cache, &mut buffer)?;
+
+            update_ggsw_cache(&mut cache, step, &self.operations);
+            for (slot, output) in outputs {
+                self.lwe_lists[slot] = output;
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Same result as [`OperationManager::execute`], but the steps of each wavefront of
+    /// [`dependency_levels`] run at the same time.
+    ///
+    /// The slots read by a wavefront are circuit-bootstrapped first, each into its own GGSWs,
+    /// then its steps read the memory as left by the previous wavefront. Their outputs are
+    /// written back once all of them are done.
+    pub fn execute_parallel(&mut self) -> Result<ExecutionStats, ProcessorError> {
+        self.validate_plan(&self.execution_plan)?;
+        let plan = &self.execution_plan;
+        let mut stats = ExecutionStats::default();
+        let mut cache = GgswCache::new();
+        for level in dependency_levels(plan) {
+            let context = StepContext::new(self);
+            let mut missing: Vec<usize> = level
+                .iter()
+                .flat_map(|&index| context.bootstrapped_inputs(&plan[index]))
+                .copied()
+                .filter(|slot| !cache.contains_key(slot))
+                .collect();
+            missing.sort_unstable();
+            missing.dedup();
+            let bootstrapped = missing
+                .par_iter()
+                .map(|&slot| context.bootstrap_slot(slot).map(|ggsws| (slot, ggsws)))
+                .collect::<Result<Vec<_>, _>>()?;
+            stats.bootstrapped_slots += bootstrapped.len();
+            cache.extend(bootstrapped);
+
+            let outputs = level
+                .par_iter()
+                .map_init(ComputationBuffers::new, |buffer, &index| {
+                    context.run_step(&plan[index], &cache, buffer)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            for (&index, outputs) in level.iter().zip(outputs) {
+                update_ggsw_cache(&mut cache, &plan[index], &self.operations);
+                for (slot, output) in outputs {
+                    self.lwe_lists[slot] = output;
                 }
+            }
+        }
+        Ok(stats)
+    }
 
-                (x, y)
-                    if matches!(
-                        x,
-                        ArithmeticOp::GT
-                            | ArithmeticOp::GTE
-                            | ArithmeticOp::LT
-                            | ArithmeticOp::LTE
-                            | ArithmeticOp::EQ
-                    ) && y >= 16 =>
-                {
-                    let fourier_bsk_view = self.fourier_bsk.as_view();
-                    let auto_keys = &self.auto_keys;
-                    let ss_key_view = self.ss_key.as_view();
-                    let ksk = &self.ksk;
-                    let params = &self.params;
-
-                    let lwe0 = self.lwe_lists[step.input_indices[0]].as_slice();
-                    let lwe1_opt = match op.op_type {
-                        OperandType::BothCipher => {
-                            Some(self.lwe_lists[step.input_indices[1]].as_slice())
-                        }
-                        _ => None,
-                    };
-
-                    let mut ggsw_lists = std::mem::take(&mut self.ggsw_lists);
-
-                    let total_lwe = lwe0.len() + lwe1_opt.map_or(0, |s| s.len());
-                    ggsw_lists
-                        .par_iter_mut()
-                        .enumerate()
-                        .take(total_lwe)
-                        .for_each(|(i, ggsw)| {
-                            let lwe = if i < lwe0.len() {
-                                &lwe0[i]
-                            } else {
-                                &lwe1_opt.unwrap()[i - lwe0.len()]
-                            };
-                            circuit_bootstrapping_4_bits_at_once_rev_tr(
-                                lwe,
-                                ggsw,
-                                fourier_bsk_view,
-                                auto_keys,
-                                ss_key_view,
-                                ksk,
-                                params,
-                            );
+    /// Every reason why `step` can not run: an unknown operation, a wrong number of inputs, a
+    /// missing immediate, a memory slot out of bounds or a slot written twice.
+    fn step_errors(&self, step: &Step) -> Vec<ProcessorError> {
+        let mut errors = Vec::new();
+        for (op_index, _) in step.outputs() {
+            match self.operations.get(op_index) {
+                Some(op) => {
+                    if step.input_indices.len() != op.input_count() {
+                        errors.push(ProcessorError::OperandCountMismatch {
+                            op: op.op,
+                            expected: op.input_count(),
+                            found: step.input_indices.len(),
                         });
-
-                    self.ggsw_lists = ggsw_lists;
-                    let temp = self.ggsw_lists.clone();
-                    match op.op_type {
-                        OperandType::BothCipher => {
-                            let (temp0, temp1) = temp.split_at(self.ggsw_lists.len() / 2);
-                            let input0 = concat_ggsw_lists(temp0.to_vec(), true);
-                            let input1 = concat_ggsw_lists(temp1.to_vec(), true);
-                            let input = vec![input0, input1];
-
-                            opmized_compare_bothcipher(
-                                &input,
-                                self.lwe_lists[step.output_index].as_mut_slice(),
-                                op.op,
-                                &self.fft,
-                            );
-                        }
-                        OperandType::CipherPlain => {
-                            let input = concat_ggsw_lists(temp, false);
-                            opmized_compare_cipherplain(
-                                &input,
-                                self.lwe_lists[step.output_index].as_mut_slice(),
-                                op.op,
-                                op.immediate.unwrap(),
-                                &self.fft,
-                            );
-                        }
-                        OperandType::PlainCipher => {
-                            let input = concat_ggsw_lists(temp, false);
-                            opmized_compare_plaincipher(
-                                &input,
-                                self.lwe_lists[step.output_index].as_mut_slice(),
-                                op.op,
-                                op.immediate.unwrap(),
-                                &self.fft,
-                            );
+                    }
+                    if op.needs_immediate() {
+                        if let Err(error) = op.require_immediate() {
+                            errors.push(error);
                         }
                     }
                 }
+                None => errors.push(ProcessorError::OperationIndexOutOfBounds {
+                    index: op_index,
+                    len: self.operations.len(),
+                }),
+            }
+        }
+        let output_indices: Vec<usize> = step.outputs().map(|(_, output)| output).collect();
+        for &index in step.input_indices.iter().chain(&output_indices) {
+            if let Err(error) = lwe_list(&self.lwe_lists, index) {
+                errors.push(error);
+            }
+        }
+        for (position, &index) in output_indices.iter().enumerate() {
+            if output_indices[..position].contains(&index) {
+                errors.push(ProcessorError::DuplicateOutput(index));
+            }
+        }
+        errors
+    }
+}
+
+/// GGSWs of the memory slots bootstrapped during one execution, by slot.
+type GgswCache = HashMap<usize, Vec<FourierGgswCiphertextList<Vec<c64>>>>;
+
+/// Keep `cache` in line with the memory once `step` has written its output slots: a MOVE
+/// carries the GGSWs of its input over, any other operation drops them.
+fn update_ggsw_cache<Scalar: UnsignedTorus>(
+    cache: &mut GgswCache,
+    step: &Step,
+    operations: &[Operation<Scalar>],
+) {
+    let moved: Vec<_> = step
+        .outputs()
+        .filter(|&(op_index, _)| operations[op_index].op == ArithmeticOp::MOVE)
+        .filter_map(|(_, output)| Some((output, cache.get(&step.input_indices[0])?.clone())))
+        .collect();
+    for (_, output) in step.outputs() {
+        cache.remove(&output);
+    }
+    cache.extend(moved);
+}
+
+/// The part of an [`OperationManager`] read by a running step. Unlike the manager, it can be
+/// shared between threads.
+struct StepContext<'a, Scalar: UnsignedTorus> {
+    operations: &'a [Operation<Scalar>],
+    lwe_lists: &'a [Vec<LweCiphertext<Vec<Scalar>>>],
+    ggsw_template: &'a [FourierGgswCiphertextList<Vec<c64>>],
+    fourier_bsk: &'a FourierLweBootstrapKeyOwned,
+    auto_keys: &'a HashMap<usize, AutomorphKey<ABox<[c64]>>>,
+    ss_key: &'a FourierGgswCiphertextList<Vec<c64>>,
+    ksk: &'a LweStoredReusedKeyswitchKey<Vec<Scalar>>,
+    params: &'a ProcessorParam<Scalar>,
+    fft: &'a Fft,
+}
+
+impl<'a, Scalar> StepContext<'a, Scalar>
+where
+    Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + Send + Sync,
+{
+    fn new(manager: &'a OperationManager<Scalar>) -> Self {
+        Self {
+            operations: &manager.operations,
+            lwe_lists: &manager.lwe_lists,
+            ggsw_template: &manager.ggsw_lists,
+            fourier_bsk: &manager.fourier_bsk,
+            auto_keys: &manager.auto_keys,
+            ss_key: &manager.ss_key,
+            ksk: &manager.ksk,
+            params: &manager.params,
+            fft: &manager.fft,
+        }
+    }
+
+    /// Memory slots read by `step` through their GGSWs, the first input first.
+    fn bootstrapped_inputs<'s>(&self, step: &'s Step) -> &'s [usize] {
+        let count = step
+            .outputs()
+            .map(|(op_index, _)| {
+                let op = &self.operations[op_index];
+                match (op.op, op.op_type) {
+                    (ArithmeticOp::MOVE, _) => 0,
+                    // only the condition is bootstrapped, the values go through a CMUX
+                    (ArithmeticOp::CSEL, _) => 1,
+                    (_, OperandType::BothCipher) => 2,
+                    _ => 1,
+                }
+            })
+            .max()
+            .unwrap_or(0);
+        &step.input_indices[..count]
+    }
 
-                _ => {
-                    let fourier_bsk_view = self.fourier_bsk.as_view();
-                    let auto_keys = &self.auto_keys;
-                    let ss_key_view = self.ss_key.as_view();
-                    let ksk = &self.ksk;
-                    let params = &self.params;
-                    let lwe0 = self.lwe_lists[step.input_indices[0]].as_slice();
-                    let lwe1_opt = match op.op_type {
-                        OperandType::BothCipher => {
-                            Some(self.lwe_lists[step.input_indices[1]].as_slice())
-                        }
-                        _ => None,
-                    };
-                    let mut ggsw_lists = std::mem::take(&mut self.ggsw_lists);
-
-                    let total_lwe = lwe0.len() + lwe1_opt.map_or(0, |s| s.len());
-                    ggsw_lists
-                        .par_iter_mut()
-                        .enumerate()
-                        .take(total_lwe)
-                        .for_each(|(i, ggsw)| {
-                            let lwe = if i < lwe0.len() {
-                                &lwe0[i]
-                            } else {
-                                &lwe1_opt.unwrap()[i - lwe0.len()]
-                            };
-
-                            circuit_bootstrapping_4_bits_at_once_rev_tr(
-                                lwe,
-                                ggsw,
-                                fourier_bsk_view,
-                                auto_keys,
-                                ss_key_view,
-                                ksk,
-                                params,
-                            );
-                        });
+    /// Run every operation of `step` on the GGSWs of its inputs in `cache` and return the new
+    /// content of each output slot. `buffer` is the scratch memory of the CMUXes, kept from
+    /// one step to the next.
+    fn run_step(
+        &self,
+        step: &Step,
+        cache: &GgswCache,
+        buffer: &mut ComputationBuffers,
+    ) -> Result<Vec<(usize, Vec<LweCiphertext<Vec<Scalar>>>)>, ProcessorError> {
+        step.outputs()
+            .map(|(op_index, output_index)| {
+                let op = &self.operations[op_index];
+                let output = self.run_operation(op, step, output_index, cache, buffer)?;
+                Ok((output_index, output))
+            })
+            .collect()
+    }
 
-                    let temp_ggsw_lists = ggsw_lists.clone();
-                    self.ggsw_lists = ggsw_lists;
+    fn run_operation(
+        &self,
+        op: &Operation<Scalar>,
+        step: &Step,
+        output_index: usize,
+        cache: &GgswCache,
+        buffer: &mut ComputationBuffers,
+    ) -> Result<Vec<LweCiphertext<Vec<Scalar>>>, ProcessorError> {
+        let is_both_cipher = match op.op_type {
+            OperandType::BothCipher => true,
+            _ => false,
+        };
+        // a missing entry ends up as an empty list for concat_ggsw_lists
+        let ggsws = |position: usize| {
+            step.input_indices
+                .get(position)
+                .and_then(|slot| cache.get(slot))
+                .map_or(&[][..], Vec::as_slice)
+        };
+        let mut output = lwe_list(self.lwe_lists, output_index)?.to_vec();
+        match op.op {
+            ArithmeticOp::CSEL => {
+                let condition = ggsws(0);
+                if condition.is_empty() {
+                    return Err(ProcessorError::EmptyGgswList);
+                }
+                csel(
+                    condition,
+                    lwe_list(self.lwe_lists, step.input_indices[1])?,
+                    lwe_list(self.lwe_lists, step.input_indices[2])?,
+                    output.as_mut_slice(),
+                    self.fft,
+                    buffer,
+                );
+            }
+            ArithmeticOp::SIGN => {
+                let immediate = op.require_immediate()?;
+                let input = concat_ggsw_lists(ggsws(0).to_vec(), true)?;
+                sign(&input, output.as_mut_slice(), immediate, self.fft);
+            }
+            ArithmeticOp::MOVE => {
+                let temp = lwe_list(self.lwe_lists, step.input_indices[0])?;
+                for (input, output) in temp.iter().zip(output.iter_mut()) {
+                    output.clone_from(input);
+                }
+            }
 
-                    let input_bits = concat_ggsw_lists(temp_ggsw_lists, is_both_cipher);
+            _ if op.is_opmized_compare() => match op.op_type {
+                OperandType::BothCipher => {
+                    let input0 = concat_ggsw_lists(ggsws(0).to_vec(), true)?;
+                    let input1 = concat_ggsw_lists(ggsws(1).to_vec(), true)?;
+                    let input = vec![input0, input1];
 
-                    op.parallel_vertical_packing_multi_lookup(
-                        self.lwe_lists[step.output_index].as_mut_slice(),
-                        &input_bits,
-                        &self.fft,
+                    opmized_compare_bothcipher(
+                        &input,
+                        output.as_mut_slice(),
+                        op.op,
+                        op.signedness,
+                        self.fft,
+                    );
+                }
+                OperandType::CipherPlain => {
+                    let immediate = op.require_immediate()?;
+                    let input = concat_ggsw_lists(ggsws(0).to_vec(), true)?;
+                    opmized_compare_cipherplain(
+                        &input,
+                        output.as_mut_slice(),
+                        op.op,
+                        op.signedness,
+                        immediate,
+                        self.fft,
+                    );
+                }
+                OperandType::PlainCipher => {
+                    let immediate = op.require_immediate()?;
+                    let input = concat_ggsw_lists(ggsws(0).to_vec(), true)?;
+                    opmized_compare_plaincipher(
+                        &input,
+                        output.as_mut_slice(),
+                        op.op,
+                        op.signedness,
+                        immediate,
+                        self.fft,
                     );
                 }
+            },
+
+            _ => {
+                let lists = if is_both_cipher {
+                    [ggsws(0), ggsws(1)].concat()
+                } else {
+                    ggsws(0).to_vec()
+                };
+                let input_bits = concat_ggsw_lists(lists, true)?;
+
+                op.parallel_vertical_packing_multi_lookup(
+                    output.as_mut_slice(),
+                    &input_bits,
+                    self.fft,
+                );
             }
         }
+        Ok(output)
+    }
+
+    /// Circuit-bootstrap the LWEs of memory slot `slot`, one GGSW list per LWE.
+    fn bootstrap_slot(
+        &self,
+        slot: usize,
+    ) -> Result<Vec<FourierGgswCiphertextList<Vec<c64>>>, ProcessorError> {
+        let fourier_bsk_view = self.fourier_bsk.as_view();
+        let auto_keys = self.auto_keys;
+        let ss_key_view = self.ss_key.as_view();
+        let ksk = self.ksk;
+        let params = self.params;
+
+        let lwes = lwe_list(self.lwe_lists, slot)?;
+        let mut ggsws = self.ggsw_template.to_vec();
+        ggsws
+            .par_iter_mut()
+            .zip(lwes.par_iter())
+            .try_for_each(|(ggsw, lwe)| {
+                circuit_bootstrapping_4_bits_at_once_rev_tr(
+                    lwe,
+                    ggsw,
+                    fourier_bsk_view,
+                    auto_keys,
+                    ss_key_view,
+                    ksk,
+                    params,
+                )
+            })?;
+        Ok(ggsws)
+    }
+}
+
+fn lwe_list<Scalar: UnsignedInteger>(
+    lwe_lists: &[Vec<LweCiphertext<Vec<Scalar>>>],
+    index: usize,
+) -> Result<&[LweCiphertext<Vec<Scalar>>], ProcessorError> {
+    lwe_lists
+        .get(index)
+        .map(Vec::as_slice)
+        .ok_or(ProcessorError::MemoryIndexOutOfBounds {
+            index,
+            len: lwe_lists.len(),
+        })
+}
+
+/// Group the steps of `plan` into wavefronts that can run at the same time, in running order.
+///
+/// A step is placed after every earlier step writing a slot it reads or writes. It may share
+/// the wavefront of an earlier step reading a slot it writes, as a wavefront reads the
+/// memory before writing to it.
+pub fn dependency_levels(plan: &[Step]) -> Vec<Vec<usize>> {
+    let mut write_level: HashMap<usize, usize> = HashMap::new();
+    let mut read_level: HashMap<usize, usize> = HashMap::new();
+    let mut levels: Vec<Vec<usize>> = Vec::new();
+    for (index, step) in plan.iter().enumerate() {
+        let output_indices: Vec<usize> = step.outputs().map(|(_, output)| output).collect();
+        let after_write = step
+            .input_indices
+            .iter()
+            .chain(&output_indices)
+            .filter_map(|slot| write_level.get(slot).map(|level| level + 1));
+        let after_read = output_indices
+            .iter()
+            .filter_map(|slot| read_level.get(slot).copied());
+        let level = after_write.chain(after_read).max().unwrap_or(0);
+
+        for &slot in &step.input_indices {
+            let read = read_level.entry(slot).or_insert(level);
+            *read = (*read).max(level);
+        }
+        for &slot in &output_indices {
+            write_level.insert(slot, level);
+        }
+        if level == levels.len() {
+            levels.push(Vec::new());
+        }
+        levels[level].push(index);
     }
+    levels
 }
 
 pub fn concat_ggsw_lists(
     lists: Vec<FourierGgswCiphertextList<Vec<c64>>>,
     is_both_cipher: bool,
-) -> FourierGgswCiphertextList<Vec<c64>> {
-    assert!(!lists.is_empty(), "GGSW lis can not be empty");
+) -> Result<FourierGgswCiphertextList<Vec<c64>>, ProcessorError> {
+    if lists.is_empty() {
+        return Err(ProcessorError::EmptyGgswList);
+    }
 
     let glwe_size = lists[0].glwe_size();
     let decomposition_level_count = lists[0].decomposition_level_count();
@@ -521,14 +807,14 @@ pub fn concat_ggsw_lists(
         }
     }
 
-    FourierGgswCiphertextList::new(
+    Ok(FourierGgswCiphertextList::new(
         all_data,
         total_count,
         glwe_size,
         poly_size,
         decomposition_base_log,
         decomposition_level_count,
-    )
+    ))
 }
 
 pub fn split_bits_high_to_low(value: usize, data_len: usize, message_size: usize) -> Vec<u64> {
@@ -539,7 +825,7 @@ pub fn split_bits_high_to_low(value: usize, data_len: usize, message_size: usize
         "data_len over usize width"
     );
 
-    let mask = (1usize << data_len) - 1;
+    let mask = usize::MAX >> (usize::BITS as usize - data_len);
     let val = value & mask;
 
     let num_chunks = (data_len + message_size - 1) / message_size;
@@ -554,33 +840,55 @@ pub fn split_bits_high_to_low(value: usize, data_len: usize, message_size: usize
     chunks
 }
 
-pub fn map_chunks_to_lwe<'a>(
-    lwe_lists: &'a Vec<Vec<LweCiphertext<Vec<u64>>>>,
+pub fn map_chunks_to_lwe<'a, Scalar: UnsignedInteger>(
+    lwe_lists: &'a Vec<Vec<LweCiphertext<Vec<Scalar>>>>,
     list_index: usize,
     value: usize,
     data_len: usize,
     message_size: usize,
-) -> Vec<(&'a LweCiphertext<Vec<u64>>, u64)> {
+) -> Result<Vec<(&'a LweCiphertext<Vec<Scalar>>, u64)>, ProcessorError> {
     let chunks = split_bits_high_to_low(value, data_len, message_size);
 
-    let lwe_vec = lwe_lists.get(list_index).expect("list_index overflow");
-
-    assert_eq!(lwe_vec.len(), chunks.len(), "LWE number blocks miss match");
-    lwe_vec.iter().zip(chunks.into_iter()).collect()
+    let len = lwe_lists.len();
+    let lwe_vec = lwe_lists
+        .get(list_index)
+        .ok_or(ProcessorError::MemoryIndexOutOfBounds {
+            index: list_index,
+            len,
+        })?;
+
+    if lwe_vec.len() != chunks.len() {
+        return Err(ProcessorError::ChunkCountMismatch {
+            expected: chunks.len(),
+            found: lwe_vec.len(),
+        });
+    }
+    Ok(lwe_vec.iter().zip(chunks.into_iter()).collect())
 }
 
-pub fn map_chunks_to_lwe_mut<'a>(
-    lwe_lists: &'a mut Vec<Vec<LweCiphertext<Vec<u64>>>>,
+pub fn map_chunks_to_lwe_mut<'a, Scalar: UnsignedInteger>(
+    lwe_lists: &'a mut Vec<Vec<LweCiphertext<Vec<Scalar>>>>,
     list_index: usize,
     value: usize,
     message_size: usize,
     extract_size: usize,
-) -> Vec<(&'a mut LweCiphertext<Vec<u64>>, u64)> {
+) -> Result<Vec<(&'a mut LweCiphertext<Vec<Scalar>>, u64)>, ProcessorError> {
     let chunks = split_bits_high_to_low(value, message_size, extract_size);
 
-    let lwe_vec = lwe_lists.get_mut(list_index).expect("list_index overflow");
-
-    assert_eq!(lwe_vec.len(), chunks.len(), "LWE number blocks miss match");
+    let len = lwe_lists.len();
+    let lwe_vec = lwe_lists
+        .get_mut(list_index)
+        .ok_or(ProcessorError::MemoryIndexOutOfBounds {
+            index: list_index,
+            len,
+        })?;
+
+    if lwe_vec.len() != chunks.len() {
+        return Err(ProcessorError::ChunkCountMismatch {
+            expected: chunks.len(),
+            found: lwe_vec.len(),
+        });
+    }
 
-    lwe_vec.iter_mut().zip(chunks.into_iter()).collect()
+    Ok(lwe_vec.iter_mut().zip(chunks.into_iter()).collect())
 }
@@ -3,4 +3,5 @@ pub mod plain_lut;
 pub mod operand;
 pub mod cipher_lut;
 pub mod operation;
-pub mod manager;
\ No newline at end of file
+pub mod manager;
+pub mod control;
\ No newline at end of file
@@ -1,7 +1,9 @@
-use num_traits::{PrimInt, Unsigned, WrappingAdd, WrappingMul, WrappingSub};
+use num_traits::{CheckedRem, PrimInt, Signed, Unsigned, WrappingAdd, WrappingMul, WrappingSub};
 use std::cmp::{max, min};
 use std::ops::{BitAnd, BitOr, BitXor, Not, Shl, Shr};
 
+use crate::error::ProcessorError;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ArithmeticOp {
     Add,
@@ -32,6 +34,15 @@ pub enum ArithmeticOp {
     SIGN,
 }
 
+/// How the LUT of an operation reads its operands and result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Signedness {
+    #[default]
+    Unsigned,
+    /// Two's complement, i.e. the semantics of `i8`, `i16` and `i32`.
+    Signed,
+}
+
 impl ArithmeticOp {
     pub fn compute<T>(&self, a: T, b: T) -> T
     where
@@ -137,71 +148,111 @@ impl ArithmeticOp {
         }
     }
 
-    /// one input of width（8、16、32），auto split to a, b
-    pub fn compute_split(&self, input: usize, bitwidth: usize) -> usize {
-        match bitwidth {
-            8 => {
-                let mask = 0xFF;
-                let a = (input & mask) as u8;
-                let b = ((input >> 8) & mask) as u8;
-                self.compute(a, b) as usize
-            }
-            16 => {
-                let mask = 0xFFFF;
-                let a = (input & mask) as u16;
-                let b = ((input >> 16) & mask) as u16;
-                self.compute(a, b) as usize
+    /// [`ArithmeticOp::compute`] on two's complement operands.
+    ///
+    /// Comparisons, MAX/MIN, Mulh, Div, Mod and SR follow the signed semantics, the other
+    /// operations give the same bits as their unsigned counterparts. Shift and rotation amounts
+    /// are taken modulo the width, a division by zero gives 0 and `MIN / -1` wraps to `MIN`.
+    pub fn compute_signed<T>(&self, a: T, b: T) -> T
+    where
+        T: PrimInt + Signed + CheckedRem + WrappingAdd + WrappingSub + WrappingMul,
+    {
+        let bits = std::mem::size_of::<T>() as u32 * 8;
+        let flag = |condition: bool| if condition { T::one() } else { T::zero() };
+        let amount = || b.to_i64().unwrap() as u32 & (bits - 1);
+        match self {
+            ArithmeticOp::Add => a.wrapping_add(&b),
+            ArithmeticOp::Sub => a.wrapping_sub(&b),
+            ArithmeticOp::Mul => a.wrapping_mul(&b),
+            ArithmeticOp::Mulh => {
+                let wide = a.to_i64().unwrap() * b.to_i64().unwrap();
+                num_traits::NumCast::from(wide >> bits).unwrap()
             }
-            32 => {
-                let mask = 0xFFFF_FFFF;
-                let a = (input & mask) as u32;
-                let b = ((input >> 32) & mask) as u32;
-                self.compute(a, b) as usize
+            ArithmeticOp::Div => {
+                if b == T::zero() {
+                    T::zero()
+                } else {
+                    a.checked_div(&b).unwrap_or(a)
+                }
             }
-            _ => panic!("Unsupported bitwidth, only 8, 16, 32 are allowed"),
+            ArithmeticOp::Mod => a.checked_rem(&b).unwrap_or(T::zero()),
+            ArithmeticOp::EQ => flag(a == b),
+            ArithmeticOp::GT => flag(a > b),
+            ArithmeticOp::LT => flag(a < b),
+            ArithmeticOp::GTE | ArithmeticOp::GTEO => flag(a >= b),
+            ArithmeticOp::LTE => flag(a <= b),
+            ArithmeticOp::MAX => max(b, a),
+            ArithmeticOp::MIN => min(b, a),
+            ArithmeticOp::RL => a.rotate_left(amount()),
+            ArithmeticOp::RR => a.rotate_right(amount()),
+            ArithmeticOp::SL => a.signed_shl(amount()),
+            ArithmeticOp::SR => a.signed_shr(amount()),
+            ArithmeticOp::OR => a | b,
+            ArithmeticOp::AND => a & b,
+            ArithmeticOp::XOR => a ^ b,
+            ArithmeticOp::NAND => !(a & b),
+            ArithmeticOp::NOT => !a,
+            ArithmeticOp::MOVE | ArithmeticOp::CSEL | ArithmeticOp::SIGN => a,
         }
     }
 
-    pub fn compute_cipher_plain(&self, input: usize, immediate: usize, bitwidth: usize) -> usize {
+    /// one input of width（8、16、32），auto split to a, b
+    pub fn compute_split(
+        &self,
+        input: usize,
+        bitwidth: usize,
+        signedness: Signedness,
+    ) -> Result<usize, ProcessorError> {
         match bitwidth {
-            8 => {
-                let a = input as u8;
-                let b = immediate as u8;
-                self.compute(a, b) as usize
-            }
-            16 => {
-                let a = input as u16;
-                let b = immediate as u16;
-                self.compute(a, b) as usize
+            8 | 16 | 32 => {
+                let mask = (1 << bitwidth) - 1;
+                let a = input & mask;
+                let b = (input >> bitwidth) & mask;
+                self.compute_width(a, b, bitwidth, signedness)
             }
-            32 => {
-                let a = input as u32;
-                let b = immediate as u32;
-                self.compute(a, b) as usize
-            }
-            _ => panic!("Unsupported bitwidth, only 8, 16, 32 are allowed"),
+            _ => Err(ProcessorError::UnsupportedBitWidth(bitwidth)),
         }
     }
 
-    pub fn compute_plain_cipher(&self, input: usize, immediate: usize, bitwidth: usize) -> usize {
-        match bitwidth {
-            8 => {
-                let a = immediate as u8;
-                let b = input as u8;
-                self.compute(a, b) as usize
-            }
-            16 => {
-                let a = immediate as u16;
-                let b = input as u16;
-                self.compute(a, b) as usize
-            }
-            32 => {
-                let a = immediate as u32;
-                let b = input as u32;
-                self.compute(a, b) as usize
-            }
-            _ => panic!("Unsupported bitwidth, only 8, 16, 32 are allowed"),
-        }
+    pub fn compute_cipher_plain(
+        &self,
+        input: usize,
+        immediate: usize,
+        bitwidth: usize,
+        signedness: Signedness,
+    ) -> Result<usize, ProcessorError> {
+        self.compute_width(input, immediate, bitwidth, signedness)
+    }
+
+    pub fn compute_plain_cipher(
+        &self,
+        input: usize,
+        immediate: usize,
+        bitwidth: usize,
+        signedness: Signedness,
+    ) -> Result<usize, ProcessorError> {
+        self.compute_width(immediate, input, bitwidth, signedness)
+    }
+
+    /// `self` on the low `bitwidth` bits of `a` and `b`, the result as its `bitwidth`-bit
+    /// pattern.
+    fn compute_width(
+        &self,
+        a: usize,
+        b: usize,
+        bitwidth: usize,
+        signedness: Signedness,
+    ) -> Result<usize, ProcessorError> {
+        let result = match (bitwidth, signedness) {
+            (8, Signedness::Unsigned) => self.compute(a as u8, b as u8) as usize,
+            (16, Signedness::Unsigned) => self.compute(a as u16, b as u16) as usize,
+            (32, Signedness::Unsigned) => self.compute(a as u32, b as u32) as usize,
+            (8, Signedness::Signed) => self.compute_signed(a as i8, b as i8) as u8 as usize,
+            (16, Signedness::Signed) => self.compute_signed(a as i16, b as i16) as u16 as usize,
+            (32, Signedness::Signed) => self.compute_signed(a as i32, b as i32) as u32 as usize,
+            _ => return Err(ProcessorError::UnsupportedBitWidth(bitwidth)),
+        };
+        Ok(result)
     }
 }
 #[cfg(test)]
@@ -213,18 +264,21 @@ mod tests {
         let input = 0x34u8 as usize | ((0x12u8 as usize) << 8);
         let op = ArithmeticOp::Add;
         assert_eq!(
-            op.compute_split(input, 8),
-            0x34u8.wrapping_add(0x12u8) as usize
+            op.compute_split(input, 8, Signedness::Unsigned),
+            Ok(0x34u8.wrapping_add(0x12u8) as usize)
         );
 
         let op = ArithmeticOp::Mul;
         assert_eq!(
-            op.compute_split(input, 8),
-            0x34u8.wrapping_mul(0x12u8) as usize
+            op.compute_split(input, 8, Signedness::Unsigned),
+            Ok(0x34u8.wrapping_mul(0x12u8) as usize)
         );
 
         let op = ArithmeticOp::AND;
-        assert_eq!(op.compute_split(input, 8), (0x34u8 & 0x12u8) as usize);
+        assert_eq!(
+            op.compute_split(input, 8, Signedness::Unsigned),
+            Ok((0x34u8 & 0x12u8) as usize)
+        );
     }
 
     #[test]
@@ -232,14 +286,14 @@ mod tests {
         let input = 0x3456u16 as usize | ((0xABCDu16 as usize) << 16);
         let op = ArithmeticOp::Sub;
         assert_eq!(
-            op.compute_split(input, 16),
-            0x3456u16.wrapping_sub(0xABCDu16) as usize
+            op.compute_split(input, 16, Signedness::Unsigned),
+            Ok(0x3456u16.wrapping_sub(0xABCDu16) as usize)
         );
 
         let op = ArithmeticOp::OR;
         assert_eq!(
-            op.compute_split(input, 16),
-            (0x3456u16 | 0xABCDu16) as usize
+            op.compute_split(input, 16, Signedness::Unsigned),
+            Ok((0x3456u16 | 0xABCDu16) as usize)
         );
     }
 
@@ -248,14 +302,130 @@ mod tests {
         let input = 0x12345678u32 as usize | ((0x9ABCDEF0u32 as usize) << 32);
         let op = ArithmeticOp::XOR;
         assert_eq!(
-            op.compute_split(input, 32),
-            (0x12345678u32 ^ 0x9ABCDEF0u32) as usize
+            op.compute_split(input, 32, Signedness::Unsigned),
+            Ok((0x12345678u32 ^ 0x9ABCDEF0u32) as usize)
         );
 
         let op = ArithmeticOp::MIN;
         assert_eq!(
-            op.compute_split(input, 32),
-            min(0x12345678u32, 0x9ABCDEF0u32) as usize
+            op.compute_split(input, 32, Signedness::Unsigned),
+            Ok(min(0x12345678u32, 0x9ABCDEF0u32) as usize)
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_op_unsupported_bitwidth() {
+        let op = ArithmeticOp::Add;
+        assert_eq!(
+            op.compute_split(0, 4, Signedness::Unsigned),
+            Err(ProcessorError::UnsupportedBitWidth(4))
+        );
+        assert_eq!(
+            op.compute_cipher_plain(0, 1, 64, Signedness::Unsigned),
+            Err(ProcessorError::UnsupportedBitWidth(64))
+        );
+    }
+
+    /// The two's complement LUT entries against the native `i8` operations.
+    fn native_i8(op: ArithmeticOp, a: i8, b: i8) -> i8 {
+        match op {
+            ArithmeticOp::Mulh => ((a as i16 * b as i16) >> 8) as i8,
+            ArithmeticOp::Div if b == 0 => 0,
+            ArithmeticOp::Div => a.wrapping_div(b),
+            ArithmeticOp::Mod if b == 0 => 0,
+            ArithmeticOp::Mod => a.wrapping_rem(b),
+            ArithmeticOp::GT => (a > b) as i8,
+            ArithmeticOp::LT => (a < b) as i8,
+            ArithmeticOp::GTE => (a >= b) as i8,
+            ArithmeticOp::LTE => (a <= b) as i8,
+            ArithmeticOp::MAX => a.max(b),
+            ArithmeticOp::MIN => a.min(b),
+            ArithmeticOp::SR => a.wrapping_shr(b as u32),
+            ArithmeticOp::Add => a.wrapping_add(b),
+            ArithmeticOp::Sub => a.wrapping_sub(b),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_arithmetic_op_signed_8bit() {
+        let ops = [
+            ArithmeticOp::Mulh,
+            ArithmeticOp::Div,
+            ArithmeticOp::Mod,
+            ArithmeticOp::GT,
+            ArithmeticOp::LT,
+            ArithmeticOp::GTE,
+            ArithmeticOp::LTE,
+            ArithmeticOp::MAX,
+            ArithmeticOp::MIN,
+            ArithmeticOp::SR,
+            ArithmeticOp::Add,
+            ArithmeticOp::Sub,
+        ];
+        for op in ops {
+            for a in i8::MIN..=i8::MAX {
+                for b in i8::MIN..=i8::MAX {
+                    let input = (a as u8 as usize) | ((b as u8 as usize) << 8);
+                    let expected = Ok(native_i8(op, a, b) as u8 as usize);
+                    assert_eq!(
+                        op.compute_split(input, 8, Signedness::Signed),
+                        expected,
+                        "{op:?}({a}, {b})"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_arithmetic_op_signed_16_32bit() {
+        let values = [i16::MIN, i16::MIN + 1, -300, -1, 0, 1, 5, 300, i16::MAX];
+        for a in values {
+            for b in values {
+                let (input, immediate) = (a as u16 as usize, b as u16 as usize);
+                let cipher_plain = |op: ArithmeticOp| {
+                    op.compute_cipher_plain(input, immediate, 16, Signedness::Signed)
+                        .map(|result| result as u16 as i16)
+                };
+                assert_eq!(cipher_plain(ArithmeticOp::LT), Ok((a < b) as i16));
+                assert_eq!(cipher_plain(ArithmeticOp::MAX), Ok(a.max(b)));
+                assert_eq!(cipher_plain(ArithmeticOp::SR), Ok(a.wrapping_shr(b as u32)));
+                if b != 0 {
+                    assert_eq!(cipher_plain(ArithmeticOp::Div), Ok(a.wrapping_div(b)));
+                    assert_eq!(cipher_plain(ArithmeticOp::Mod), Ok(a.wrapping_rem(b)));
+                }
+            }
+        }
+
+        let values = [i32::MIN, -70_000, -1, 0, 1, 3, 70_000, i32::MAX];
+        for a in values {
+            for b in values {
+                // the immediate is the left operand
+                let (input, immediate) = (b as u32 as usize, a as u32 as usize);
+                let plain_cipher = |op: ArithmeticOp| {
+                    op.compute_plain_cipher(input, immediate, 32, Signedness::Signed)
+                        .map(|result| result as u32 as i32)
+                };
+                assert_eq!(plain_cipher(ArithmeticOp::GT), Ok((a > b) as i32));
+                assert_eq!(plain_cipher(ArithmeticOp::MIN), Ok(a.min(b)));
+                assert_eq!(plain_cipher(ArithmeticOp::SR), Ok(a.wrapping_shr(b as u32)));
+                if b != 0 {
+                    assert_eq!(plain_cipher(ArithmeticOp::Div), Ok(a.wrapping_div(b)));
+                    assert_eq!(plain_cipher(ArithmeticOp::Mod), Ok(a.wrapping_rem(b)));
+                }
+            }
+        }
+
+        // the unsigned reading of the same bits
+        let op = ArithmeticOp::LT;
+        assert_eq!(
+            op.compute_cipher_plain(0xFFFF, 1, 16, Signedness::Signed),
+            Ok(1)
+        );
+        assert_eq!(
+            op.compute_cipher_plain(0xFFFF, 1, 16, Signedness::Unsigned),
+            Ok(0)
         );
     }
 }
@@ -1,9 +1,12 @@
-use crate::operations::{
-    cipher_lut::generate_lut_from_vecs_auto,
-    operand::ArithmeticOp,
-    plain_lut::{
-        build_split_lut_tables, build_split_lut_tables_cipher_plain,
-        build_split_lut_tables_plain_cipher,
+use crate::{
+    error::ProcessorError,
+    operations::{
+        cipher_lut::generate_lut_from_vecs_auto,
+        operand::{ArithmeticOp, Signedness},
+        plain_lut::{
+            build_split_lut_tables, build_split_lut_tables_cipher_plain,
+            build_split_lut_tables_plain_cipher,
+        },
     },
 };
 use aligned_vec::CACHELINE_ALIGN;
@@ -24,104 +27,134 @@ use tfhe::core_crypto::{
     prelude::*,
 };
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum OperandType {
     BothCipher,
     PlainCipher,
     CipherPlain,
 }
 
-pub struct Operation {
+pub struct Operation<Scalar: UnsignedTorus> {
     // 操作类型和参数配置
     pub op: ArithmeticOp,
     pub op_type: OperandType,
+    pub signedness: Signedness,
     pub bit_width: usize,  //8,16,32
     pub chunk_size: usize, // 1,2,4
     // 只存密文查找表
-    pub cipher_lut: Vec<PolynomialList<Vec<u64>>>,
+    pub cipher_lut: Vec<PolynomialList<Vec<Scalar>>>,
     pub lut_pack_size: usize, // 每个多项式能打包多少张表
     pub immediate: Option<usize>,
 }
 
-impl Operation {
+impl<Scalar> Operation<Scalar>
+where
+    Scalar: UnsignedTorus + CastFrom<usize> + Send + Sync,
+{
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         op: ArithmeticOp,
         op_type: OperandType,
+        signedness: Signedness,
         bit_width: usize,
         chunk_size: usize,
         poly_size: tfhe::boolean::prelude::PolynomialSize,
-        delta: u64,
+        delta: Scalar,
         immediate: Option<usize>,
-    ) -> Self {
+    ) -> Result<Self, ProcessorError> {
+        let mut operation = Self {
+            op,
+            op_type,
+            signedness,
+            bit_width,
+            chunk_size,
+            cipher_lut: Vec::new(),
+            lut_pack_size: 0,
+            immediate,
+        };
+        // MOVE、CSEL、SIGN 和 opmised compare 操作没有查找表
         if matches!(
             op,
             ArithmeticOp::MOVE | ArithmeticOp::CSEL | ArithmeticOp::SIGN
-        ) {
-            Self {
-                op,
-                op_type,
+        ) || operation.is_opmized_compare()
+        {
+            return Ok(operation);
+        }
+
+        let plain_lut = match op_type {
+            OperandType::BothCipher => build_split_lut_tables(
                 bit_width,
+                vec![bit_width, bit_width],
                 chunk_size,
-                cipher_lut: Vec::new(), // 没有查找表
-                lut_pack_size: 0,
-                immediate,
-            }
-        } else if matches!(
-            op,
+                &op,
+                signedness,
+            )?,
+            OperandType::PlainCipher => build_split_lut_tables_plain_cipher(
+                bit_width,
+                immediate.ok_or(ProcessorError::MissingImmediate(op))?,
+                vec![bit_width],
+                chunk_size,
+                &op,
+                signedness,
+            )?,
+            OperandType::CipherPlain => build_split_lut_tables_cipher_plain(
+                bit_width,
+                immediate.ok_or(ProcessorError::MissingImmediate(op))?,
+                vec![bit_width],
+                chunk_size,
+                &op,
+                signedness,
+            )?,
+        };
+        (operation.cipher_lut, operation.lut_pack_size) =
+            generate_lut_from_vecs_auto(&plain_lut, poly_size, delta);
+        Ok(operation)
+    }
+
+    /// Whether the operation is a 16 or 32-bit comparison run by the LUT-free circuits of
+    /// `opmized_operations::compare`.
+    ///
+    /// A signed comparison runs there too, with the outcomes of the sign bit swapped.
+    pub fn is_opmized_compare(&self) -> bool {
+        matches!(
+            self.op,
             ArithmeticOp::GT
                 | ArithmeticOp::GTE
                 | ArithmeticOp::LT
                 | ArithmeticOp::LTE
                 | ArithmeticOp::EQ
-        ) && bit_width >= 16
-        {
-            Self {
-                op,
-                op_type,
-                bit_width,
-                chunk_size,
-                cipher_lut: Vec::new(), // opmised compare 操作没有查找表
-                lut_pack_size: 0,
-                immediate,
-            }
-        } else {
-            let plain_lut = match op_type {
-                OperandType::BothCipher => {
-                    build_split_lut_tables(bit_width, vec![bit_width, bit_width], chunk_size, &op)
-                }
-                OperandType::PlainCipher => build_split_lut_tables_plain_cipher(
-                    bit_width,
-                    immediate.unwrap(),
-                    vec![bit_width],
-                    chunk_size,
-                    &op,
-                ),
-                OperandType::CipherPlain => build_split_lut_tables_cipher_plain(
-                    bit_width,
-                    immediate.unwrap(),
-                    vec![bit_width],
-                    chunk_size,
-                    &op,
-                ),
-            };
+        ) && self.bit_width >= 16
+    }
 
-            let (cipher_lut, lut_pack_size) =
-                generate_lut_from_vecs_auto(&plain_lut, poly_size, delta);
-            Self {
-                op,
-                op_type,
-                bit_width,
-                chunk_size,
-                cipher_lut,
-                lut_pack_size,
-                immediate,
-            }
+    /// Number of memory slots read by a step running this operation.
+    pub fn input_count(&self) -> usize {
+        match (self.op, self.op_type) {
+            (ArithmeticOp::MOVE, _) => 1,
+            (ArithmeticOp::CSEL, _) => 3,
+            (_, OperandType::BothCipher) => 2,
+            _ => 1,
         }
     }
 
+    /// Whether a step running this operation reads [`Operation::immediate`].
+    pub fn needs_immediate(&self) -> bool {
+        match (self.op, self.op_type) {
+            (ArithmeticOp::MOVE | ArithmeticOp::CSEL, _) => false,
+            (ArithmeticOp::SIGN, _) => true,
+            (_, OperandType::BothCipher) => false,
+            _ => true,
+        }
+    }
+
+    /// The plaintext operand of a `CipherPlain` or `PlainCipher` operation.
+    pub fn require_immediate(&self) -> Result<usize, ProcessorError> {
+        self.immediate
+            .ok_or(ProcessorError::MissingImmediate(self.op))
+    }
+
     pub fn parallel_vertical_packing_multi_lookup(
         &self,
-        lwe_outs: &mut [LweCiphertext<Vec<u64>>],
+        lwe_outs: &mut [LweCiphertext<Vec<Scalar>>],
         ggsw_list: &FourierGgswCiphertextList<Vec<c64>>,
         fft: &Fft,
     ) {
@@ -140,7 +173,7 @@ impl Operation {
             .zip(lwe_outs.par_chunks_mut(group_size))
             .for_each(|(lut, lwe_group)| {
                 let mut local_buffer = ComputationBuffers::new();
-                let need = vertical_packing_scratch::<u64>(
+                let need = vertical_packing_scratch::<Scalar>(
                     ggsw_view.glwe_size(),
                     ggsw_view.polynomial_size(),
                     lut.polynomial_count(),
@@ -172,7 +205,7 @@ impl Operation {
 
     pub fn vertical_packing_multi_lookup(
         &self,
-        lwe_outs: &mut [LweCiphertext<Vec<u64>>],
+        lwe_outs: &mut [LweCiphertext<Vec<Scalar>>],
         ggsw_list: &FourierGgswCiphertextList<Vec<c64>>,
         fft: &Fft,
         buffer: &mut ComputationBuffers,
@@ -191,7 +224,7 @@ impl Operation {
         );
         let lut = &self.cipher_lut[0];
         buffer.resize(
-            vertical_packing_scratch::<u64>(
+            vertical_packing_scratch::<Scalar>(
                 ggsw_list.glwe_size(),
                 ggsw_list.polynomial_size(),
                 lut.polynomial_count(),
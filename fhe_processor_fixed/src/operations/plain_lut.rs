@@ -1,47 +1,56 @@
-use crate::operations::{
-    mask_chunk::{masking_chunk_msb, masking_chunk_msb_decode},
-    operand::ArithmeticOp,
+use crate::{
+    error::ProcessorError,
+    operations::{
+        mask_chunk::{masking_chunk_msb, masking_chunk_msb_decode},
+        operand::{ArithmeticOp, Signedness},
+    },
 };
 
-pub fn get_plain_cipher_cipher(plain_log: usize, op: &ArithmeticOp) -> Vec<usize> {
+pub fn get_plain_cipher_cipher(
+    plain_log: usize,
+    op: &ArithmeticOp,
+    signedness: Signedness,
+) -> Result<Vec<usize>, ProcessorError> {
     let lut_input_size: usize = 1 << (plain_log * 2);
     let mut lut = Vec::with_capacity(lut_input_size);
 
     for i in 0..lut_input_size {
-        let result = op.compute_split(i, plain_log);
+        let result = op.compute_split(i, plain_log, signedness)?;
         lut.push(result);
     }
-    lut
+    Ok(lut)
 }
 
 pub fn get_plain_lut_cipher_plain(
     plain_log: usize,
     immediate: usize,
     op: &ArithmeticOp,
-) -> Vec<usize> {
+    signedness: Signedness,
+) -> Result<Vec<usize>, ProcessorError> {
     let lut_input_size: usize = 1 << (plain_log);
     let mut lut = Vec::with_capacity(lut_input_size);
 
     for i in 0..lut_input_size {
-        let result = op.compute_cipher_plain(i, immediate, plain_log);
+        let result = op.compute_cipher_plain(i, immediate, plain_log, signedness)?;
         lut.push(result);
     }
-    lut
+    Ok(lut)
 }
 
 pub fn get_plain_lut_plain_cipher(
     plain_log: usize,
     immediate: usize,
     op: &ArithmeticOp,
-) -> Vec<usize> {
+    signedness: Signedness,
+) -> Result<Vec<usize>, ProcessorError> {
     let lut_input_size: usize = 1 << (plain_log);
     let mut lut = Vec::with_capacity(lut_input_size);
 
     for i in 0..lut_input_size {
-        let result = op.compute_plain_cipher(i, immediate, plain_log);
+        let result = op.compute_plain_cipher(i, immediate, plain_log, signedness)?;
         lut.push(result);
     }
-    lut
+    Ok(lut)
 }
 
 pub fn adjust_lut_with_masking(
@@ -138,25 +147,34 @@ pub fn split_adjusted_lut_by_chunk(
 /// - `input_bitwidths`: Array of bitwidths for each input (e.g., `vec![8, 8]`)
 /// - `chunk_size`: Bitwidth per chunk (e.g., 4)
 /// - `op`: Arithmetic operator
+/// - `signedness`: Whether the operands and result are two's complement
 ///
 /// # Returns
 /// - `Vec<Vec<usize>>`: Chunked lookup tables (each inner `Vec` represents one chunk’s table)
 ///
+/// # Errors
+/// - `ProcessorError::UnsupportedBitWidth` if `plain_log` is not 8, 16 or 32
+///
 /// # Example
 /// ```ignore
-/// let chunk_tables = build_split_lut_tables(8, vec![8, 8], 4, &ArithmeticOp::Add);
+/// let chunk_tables = build_split_lut_tables(8, vec![8, 8], 4, &ArithmeticOp::Add, Signedness::Unsigned);
 /// ```
 pub fn build_split_lut_tables(
     plain_log: usize,
     input_bitwidths: Vec<usize>,
     chunk_size: usize,
     op: &ArithmeticOp,
-) -> Vec<Vec<usize>> {
-    let plain_lut = get_plain_cipher_cipher(plain_log, op);
+    signedness: Signedness,
+) -> Result<Vec<Vec<usize>>, ProcessorError> {
+    let plain_lut = get_plain_cipher_cipher(plain_log, op, signedness)?;
 
     let adjusted_lut = adjust_lut_with_masking_decode(&plain_lut, &input_bitwidths, chunk_size);
 
-    split_adjusted_lut_by_chunk(&adjusted_lut, plain_log, chunk_size)
+    Ok(split_adjusted_lut_by_chunk(
+        &adjusted_lut,
+        plain_log,
+        chunk_size,
+    ))
 }
 
 pub fn build_split_lut_tables_cipher_plain(
@@ -165,12 +183,17 @@ pub fn build_split_lut_tables_cipher_plain(
     input_bitwidths: Vec<usize>,
     chunk_size: usize,
     op: &ArithmeticOp,
-) -> Vec<Vec<usize>> {
-    let plain_lut = get_plain_lut_cipher_plain(plain_log, immediate, op);
+    signedness: Signedness,
+) -> Result<Vec<Vec<usize>>, ProcessorError> {
+    let plain_lut = get_plain_lut_cipher_plain(plain_log, immediate, op, signedness)?;
 
     let adjusted_lut = adjust_lut_with_masking_decode(&plain_lut, &input_bitwidths, chunk_size);
 
-    split_adjusted_lut_by_chunk(&adjusted_lut, plain_log, chunk_size)
+    Ok(split_adjusted_lut_by_chunk(
+        &adjusted_lut,
+        plain_log,
+        chunk_size,
+    ))
 }
 
 pub fn build_split_lut_tables_plain_cipher(
@@ -179,10 +202,15 @@ pub fn build_split_lut_tables_plain_cipher(
     input_bitwidths: Vec<usize>,
     chunk_size: usize,
     op: &ArithmeticOp,
-) -> Vec<Vec<usize>> {
-    let plain_lut = get_plain_lut_plain_cipher(plain_log, immediate, op);
+    signedness: Signedness,
+) -> Result<Vec<Vec<usize>>, ProcessorError> {
+    let plain_lut = get_plain_lut_plain_cipher(plain_log, immediate, op, signedness)?;
 
     let adjusted_lut = adjust_lut_with_masking_decode(&plain_lut, &input_bitwidths, chunk_size);
 
-    split_adjusted_lut_by_chunk(&adjusted_lut, plain_log, chunk_size)
+    Ok(split_adjusted_lut_by_chunk(
+        &adjusted_lut,
+        plain_log,
+        chunk_size,
+    ))
 }
@@ -5,24 +5,25 @@ use tfhe::{
         extract_lwe_sample_from_glwe_ciphertext, trivially_encrypt_glwe_ciphertext,
         trivially_encrypt_lwe_ciphertext, ComputationBuffers, Fft, FourierGgswCiphertext,
         FourierGgswCiphertextList, GlweCiphertext, LweCiphertext, MonomialDegree, Plaintext,
-        PlaintextList,
+        PlaintextList, UnsignedTorus,
     },
     shortint::wopbs::PlaintextCount,
 };
 
-use crate::operations::operand::ArithmeticOp;
+use crate::operations::operand::{ArithmeticOp, Signedness};
 
-pub fn opmized_compare_bothcipher(
+pub fn opmized_compare_bothcipher<Scalar: UnsignedTorus>(
     input: &Vec<FourierGgswCiphertextList<Vec<c64>>>,
-    lwe_outs: &mut [LweCiphertext<Vec<u64>>],
+    lwe_outs: &mut [LweCiphertext<Vec<Scalar>>],
     op: ArithmeticOp,
+    signedness: Signedness,
     fft: &Fft,
 ) {
     let glwe_size = input[0].glwe_size();
     let poly_size = input[0].polynomial_size();
     let cipher_modulus = lwe_outs[0].ciphertext_modulus();
 
-    let mut equiv = GlweCiphertext::new(0, glwe_size, poly_size, cipher_modulus);
+    let mut equiv = GlweCiphertext::new(Scalar::ZERO, glwe_size, poly_size, cipher_modulus);
     let mut greater = equiv.clone();
     let mut less = equiv.clone();
 
@@ -35,9 +36,9 @@ pub fn opmized_compare_bothcipher(
     let mut mid_1 = equiv.clone();
     let mut mid_0 = equiv.clone();
 
-    let encoded_msg_1 = 1_u64 << 60;
+    let encoded_msg_1 = Scalar::ONE << (Scalar::BITS - 4);
     let plaintext_list_1 = PlaintextList::new(encoded_msg_1, PlaintextCount(poly_size.0));
-    let plaintext_list_0 = PlaintextList::new(0, PlaintextCount(poly_size.0));
+    let plaintext_list_0 = PlaintextList::new(Scalar::ZERO, PlaintextCount(poly_size.0));
     match op {
         ArithmeticOp::GT => {
             trivially_encrypt_glwe_ciphertext(&mut greater, &plaintext_list_1);
@@ -69,10 +70,11 @@ pub fn opmized_compare_bothcipher(
 
     let list_a = &input[0];
     let list_b = &input[1];
+    let count = list_a.count().min(list_b.count());
     let fft_viwe = fft.as_view();
     let mut buffer = ComputationBuffers::new();
     let buffer_size_req =
-        cmux_assign_mem_optimized_requirement::<u64>(glwe_size, poly_size, fft_viwe)
+        cmux_assign_mem_optimized_requirement::<Scalar>(glwe_size, poly_size, fft_viwe)
             .unwrap()
             .unaligned_bytes_required();
 
@@ -115,8 +117,9 @@ pub fn opmized_compare_bothcipher(
             }
 
             3 => {
-                local_cmux(&greater, &equiv_1, &b, &mut mid_1, &mut buffer, fft);
-                local_cmux(&equiv_0, &less, &b, &mut mid_0, &mut buffer, fft);
+                let (greater, less) = msb_outcomes(&greater, &less, signedness, index + 1 == count);
+                local_cmux(greater, &equiv_1, &b, &mut mid_1, &mut buffer, fft);
+                local_cmux(&equiv_0, less, &b, &mut mid_0, &mut buffer, fft);
                 local_cmux(&mid_0, &mid_1, &a, &mut equiv, &mut buffer, fft);
             }
             _ => {
@@ -125,16 +128,17 @@ pub fn opmized_compare_bothcipher(
         }
     }
     for lwe in lwe_outs.iter_mut() {
-        trivially_encrypt_lwe_ciphertext(lwe, Plaintext(0));
+        trivially_encrypt_lwe_ciphertext(lwe, Plaintext(Scalar::ZERO));
     }
     let result = lwe_outs.last_mut().unwrap();
     extract_lwe_sample_from_glwe_ciphertext(&equiv, result, MonomialDegree(0));
 }
 
-pub fn opmized_compare_cipherplain(
+pub fn opmized_compare_cipherplain<Scalar: UnsignedTorus>(
     input: &FourierGgswCiphertextList<Vec<c64>>,
-    lwe_outs: &mut [LweCiphertext<Vec<u64>>],
+    lwe_outs: &mut [LweCiphertext<Vec<Scalar>>],
     op: ArithmeticOp,
+    signedness: Signedness,
     immediate: usize,
     fft: &Fft,
 ) {
@@ -143,7 +147,7 @@ pub fn opmized_compare_cipherplain(
     let poly_size = input.polynomial_size();
     let cipher_modulus = lwe_outs[0].ciphertext_modulus();
 
-    let mut equiv = GlweCiphertext::new(0, glwe_size, poly_size, cipher_modulus);
+    let mut equiv = GlweCiphertext::new(Scalar::ZERO, glwe_size, poly_size, cipher_modulus);
     let mut greater = equiv.clone();
     let mut less = equiv.clone();
 
@@ -156,9 +160,9 @@ pub fn opmized_compare_cipherplain(
     let mut mid_1 = equiv.clone();
     let mut mid_0 = equiv.clone();
 
-    let encoded_msg_1 = 1_u64 << 60;
+    let encoded_msg_1 = Scalar::ONE << (Scalar::BITS - 4);
     let plaintext_list_1 = PlaintextList::new(encoded_msg_1, PlaintextCount(poly_size.0));
-    let plaintext_list_0 = PlaintextList::new(0, PlaintextCount(poly_size.0));
+    let plaintext_list_0 = PlaintextList::new(Scalar::ZERO, PlaintextCount(poly_size.0));
     match op {
         ArithmeticOp::GT => {
             trivially_encrypt_glwe_ciphertext(&mut greater, &plaintext_list_1);
@@ -189,11 +193,12 @@ pub fn opmized_compare_cipherplain(
     }
 
     let plain_bits = encode_vec_nibbles(immediate, bits);
+    let count = input.count();
 
     let fft_viwe = fft.as_view();
     let mut buffer = ComputationBuffers::new();
     let buffer_size_req =
-        cmux_assign_mem_optimized_requirement::<u64>(glwe_size, poly_size, fft_viwe)
+        cmux_assign_mem_optimized_requirement::<Scalar>(glwe_size, poly_size, fft_viwe)
             .unwrap()
             .unaligned_bytes_required();
 
@@ -236,8 +241,9 @@ pub fn opmized_compare_cipherplain(
             }
 
             3 => {
-                plain_mux(&greater, &equiv_1, &b, &mut mid_1);
-                plain_mux(&equiv_0, &less, &b, &mut mid_0);
+                let (greater, less) = msb_outcomes(&greater, &less, signedness, index + 1 == count);
+                plain_mux(greater, &equiv_1, &b, &mut mid_1);
+                plain_mux(&equiv_0, less, &b, &mut mid_0);
                 local_cmux(&mid_0, &mid_1, &a, &mut equiv, &mut buffer, fft);
             }
             _ => {
@@ -246,16 +252,17 @@ pub fn opmized_compare_cipherplain(
         }
     }
     for lwe in lwe_outs.iter_mut() {
-        trivially_encrypt_lwe_ciphertext(lwe, Plaintext(0));
+        trivially_encrypt_lwe_ciphertext(lwe, Plaintext(Scalar::ZERO));
     }
     let result = lwe_outs.last_mut().unwrap();
     extract_lwe_sample_from_glwe_ciphertext(&equiv, result, MonomialDegree(0));
 }
 
-pub fn opmized_compare_plaincipher(
+pub fn opmized_compare_plaincipher<Scalar: UnsignedTorus>(
     input: &FourierGgswCiphertextList<Vec<c64>>,
-    lwe_outs: &mut [LweCiphertext<Vec<u64>>],
+    lwe_outs: &mut [LweCiphertext<Vec<Scalar>>],
     op: ArithmeticOp,
+    signedness: Signedness,
     immediate: usize,
     fft: &Fft,
 ) {
@@ -263,7 +270,7 @@ pub fn opmized_compare_plaincipher(
     let poly_size = input.polynomial_size();
     let cipher_modulus = lwe_outs[0].ciphertext_modulus();
 
-    let mut equiv = GlweCiphertext::new(0, glwe_size, poly_size, cipher_modulus);
+    let mut equiv = GlweCiphertext::new(Scalar::ZERO, glwe_size, poly_size, cipher_modulus);
     let mut greater = equiv.clone();
     let mut less = equiv.clone();
 
@@ -276,11 +283,12 @@ pub fn opmized_compare_plaincipher(
     let mut mid_1 = equiv.clone();
     let mut mid_0 = equiv.clone();
 
-    let encoded_msg_1 = 1_u64 << 60;
+    let encoded_msg_1 = Scalar::ONE << (Scalar::BITS - 4);
     let plaintext_list_1 = PlaintextList::new(encoded_msg_1, PlaintextCount(poly_size.0));
-    let plaintext_list_0 = PlaintextList::new(0, PlaintextCount(poly_size.0));
+    let plaintext_list_0 = PlaintextList::new(Scalar::ZERO, PlaintextCount(poly_size.0));
     let bits = input.count() * 4;
     let plain_bits = encode_vec_nibbles(immediate, bits);
+    let count = input.count();
     match op {
         ArithmeticOp::GT => {
             trivially_encrypt_glwe_ciphertext(&mut greater, &plaintext_list_1);
@@ -313,7 +321,7 @@ pub fn opmized_compare_plaincipher(
     let fft_viwe = fft.as_view();
     let mut buffer = ComputationBuffers::new();
     let buffer_size_req =
-        cmux_assign_mem_optimized_requirement::<u64>(glwe_size, poly_size, fft_viwe)
+        cmux_assign_mem_optimized_requirement::<Scalar>(glwe_size, poly_size, fft_viwe)
             .unwrap()
             .unaligned_bytes_required();
 
@@ -355,8 +363,9 @@ pub fn opmized_compare_plaincipher(
             }
 
             3 => {
-                local_cmux(&greater, &equiv_1, &b, &mut mid_1, &mut buffer, fft);
-                local_cmux(&equiv_0, &less, &b, &mut mid_0, &mut buffer, fft);
+                let (greater, less) = msb_outcomes(&greater, &less, signedness, index + 1 == count);
+                local_cmux(greater, &equiv_1, &b, &mut mid_1, &mut buffer, fft);
+                local_cmux(&equiv_0, less, &b, &mut mid_0, &mut buffer, fft);
                 plain_mux(&mid_0, &mid_1, &a, &mut equiv);
             }
             _ => {
@@ -365,16 +374,34 @@ pub fn opmized_compare_plaincipher(
         }
     }
     for lwe in lwe_outs.iter_mut() {
-        trivially_encrypt_lwe_ciphertext(lwe, Plaintext(0));
+        trivially_encrypt_lwe_ciphertext(lwe, Plaintext(Scalar::ZERO));
     }
     let result = lwe_outs.last_mut().unwrap();
     extract_lwe_sample_from_glwe_ciphertext(&equiv, result, MonomialDegree(0));
 }
-fn local_cmux(
-    input_0: &GlweCiphertext<Vec<u64>>,
-    input_1: &GlweCiphertext<Vec<u64>>,
+
+/// The outcomes the most significant bit selects when the two operands differ there.
+///
+/// Two's complement makes a set sign bit the smaller operand, so a signed comparison swaps
+/// `greater` and `less` on that bit, the same as flipping the sign bit of both operands.
+fn msb_outcomes<'a, T>(
+    greater: &'a T,
+    less: &'a T,
+    signedness: Signedness,
+    is_msb: bool,
+) -> (&'a T, &'a T) {
+    if is_msb && signedness == Signedness::Signed {
+        (less, greater)
+    } else {
+        (greater, less)
+    }
+}
+
+fn local_cmux<Scalar: UnsignedTorus>(
+    input_0: &GlweCiphertext<Vec<Scalar>>,
+    input_1: &GlweCiphertext<Vec<Scalar>>,
     control: &FourierGgswCiphertext<&[c64]>,
-    mut output: &mut GlweCiphertext<Vec<u64>>,
+    mut output: &mut GlweCiphertext<Vec<Scalar>>,
     buffer: &mut ComputationBuffers,
     fft: &Fft,
 ) {
@@ -385,11 +412,11 @@ fn local_cmux(
     cmux_assign_mem_optimized(&mut output, &mut temp, control, fft_view, stack);
 }
 
-fn plain_mux(
-    input_0: &GlweCiphertext<Vec<u64>>,
-    input_1: &GlweCiphertext<Vec<u64>>,
+fn plain_mux<Scalar: UnsignedTorus>(
+    input_0: &GlweCiphertext<Vec<Scalar>>,
+    input_1: &GlweCiphertext<Vec<Scalar>>,
     control: &usize,
-    output: &mut GlweCiphertext<Vec<u64>>,
+    output: &mut GlweCiphertext<Vec<Scalar>>,
 ) {
     match control {
         0 => {
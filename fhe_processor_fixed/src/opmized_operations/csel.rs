@@ -0,0 +1,79 @@
+use concrete_fft::c64;
+use tfhe::core_crypto::prelude::{
+    cmux_assign_mem_optimized, cmux_assign_mem_optimized_requirement,
+    extract_lwe_sample_from_glwe_ciphertext, ComputationBuffers, Fft, FourierGgswCiphertextList,
+    GlweCiphertext, LweCiphertext, MonomialDegree, UnsignedTorus,
+};
+
+/// `lwe_outs = condition != 0 ? lwe_a : lwe_b`, chunk by chunk.
+///
+/// `condition` holds the GGSW lists of the condition chunks. Starting from `lwe_b`, the GGSW of
+/// every bit selects `lwe_a` if the bit is set, so one set bit is enough to keep `lwe_a`.
+/// `buffer` is resized as needed, so it can be reused from one call to the next.
+pub fn csel<Scalar: UnsignedTorus>(
+    condition: &[FourierGgswCiphertextList<Vec<c64>>],
+    lwe_a: &[LweCiphertext<Vec<Scalar>>],
+    lwe_b: &[LweCiphertext<Vec<Scalar>>],
+    lwe_outs: &mut [LweCiphertext<Vec<Scalar>>],
+    fft: &Fft,
+    buffer: &mut ComputationBuffers,
+) {
+    let glwe_size = condition[0].glwe_size();
+    let poly_size = condition[0].polynomial_size();
+    let cipher_modulus = lwe_outs[0].ciphertext_modulus();
+
+    let fft_view = fft.as_view();
+    buffer.resize(
+        cmux_assign_mem_optimized_requirement::<Scalar>(glwe_size, poly_size, fft_view)
+            .unwrap()
+            .unaligned_bytes_required(),
+    );
+
+    let mut glwe_a = GlweCiphertext::new(Scalar::ZERO, glwe_size, poly_size, cipher_modulus);
+    let mut glwe_out = glwe_a.clone();
+    // the CMUX leaves its second operand overwritten
+    let mut glwe_diff = glwe_a.clone();
+    for ((a, b), out) in lwe_a.iter().zip(lwe_b.iter()).zip(lwe_outs.iter_mut()) {
+        lwe_to_glwe(a, &mut glwe_a);
+        lwe_to_glwe(b, &mut glwe_out);
+        for list in condition {
+            for selector in list.as_view().into_ggsw_iter() {
+                // glwe_out = selector ? glwe_a : glwe_out
+                glwe_diff.as_mut().copy_from_slice(glwe_a.as_ref());
+                cmux_assign_mem_optimized(
+                    &mut glwe_out,
+                    &mut glwe_diff,
+                    &selector,
+                    fft_view,
+                    buffer.stack(),
+                );
+            }
+        }
+        extract_lwe_sample_from_glwe_ciphertext(&glwe_out, out, MonomialDegree(0));
+    }
+}
+
+/// Put `lwe`, encrypted under the flattened key of `glwe`, in the constant coefficient of
+/// `glwe`, the inverse of a sample extraction at degree 0.
+fn lwe_to_glwe<Scalar: UnsignedTorus>(
+    lwe: &LweCiphertext<Vec<Scalar>>,
+    glwe: &mut GlweCiphertext<Vec<Scalar>>,
+) {
+    let poly_size = glwe.polynomial_size().0;
+    let (lwe_mask, lwe_body) = lwe.as_ref().split_at(lwe.lwe_size().0 - 1);
+    let glwe_data = glwe.as_mut();
+    glwe_data.fill(Scalar::ZERO);
+
+    let (glwe_mask, glwe_body) = glwe_data.split_at_mut(lwe_mask.len());
+    // X^(N-i) * X^i = -1, so the mask of degree i moves to degree N - i with its sign flipped
+    for (poly, mask) in glwe_mask
+        .chunks_exact_mut(poly_size)
+        .zip(lwe_mask.chunks_exact(poly_size))
+    {
+        poly[0] = mask[0];
+        for (i, &coefficient) in mask.iter().enumerate().skip(1) {
+            poly[poly_size - i] = coefficient.wrapping_neg();
+        }
+    }
+    glwe_body[0] = lwe_body[0];
+}
@@ -1,2 +1,3 @@
 pub mod compare;
+pub mod csel;
 pub mod sign;
\ No newline at end of file
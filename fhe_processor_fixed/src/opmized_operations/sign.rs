@@ -3,15 +3,15 @@ use tfhe::{
     core_crypto::prelude::{
         cmux_assign_mem_optimized, cmux_assign_mem_optimized_requirement,
         extract_lwe_sample_from_glwe_ciphertext, trivially_encrypt_glwe_ciphertext,
-        ComputationBuffers, Fft, FourierGgswCiphertext, FourierGgswCiphertextList,
-        GlweCiphertext, LweCiphertext, MonomialDegree, PlaintextList,
+        ComputationBuffers, Fft, FourierGgswCiphertext, FourierGgswCiphertextList, GlweCiphertext,
+        LweCiphertext, MonomialDegree, PlaintextList, UnsignedTorus,
     },
     shortint::wopbs::PlaintextCount,
 };
 
-pub fn sign(
+pub fn sign<Scalar: UnsignedTorus>(
     input: &FourierGgswCiphertextList<Vec<c64>>,
-    lwe_outs: &mut [LweCiphertext<Vec<u64>>],
+    lwe_outs: &mut [LweCiphertext<Vec<Scalar>>],
     immediate: usize,
     fft: &Fft,
 ) {
@@ -20,7 +20,7 @@ pub fn sign(
     let poly_size = input.polynomial_size();
     let cipher_modulus = lwe_outs[0].ciphertext_modulus();
 
-    let mut equiv = GlweCiphertext::new(0, glwe_size, poly_size, cipher_modulus);
+    let mut equiv = GlweCiphertext::new(Scalar::ZERO, glwe_size, poly_size, cipher_modulus);
     let mut greater = equiv.clone();
     let mut less = equiv.clone();
 
@@ -33,9 +33,9 @@ pub fn sign(
     let mut mid_1 = equiv.clone();
     let mut mid_0 = equiv.clone();
 
-    let encoded_msg_0000 = 0_u64 << 60;
-    let encoded_msg_0001 = 1_u64 << 60;
-    let encoded_msg_1111 = 15_u64 << 60;
+    let encoded_msg_0000 = Scalar::ZERO;
+    let encoded_msg_0001 = Scalar::ONE << (Scalar::BITS - 4);
+    let encoded_msg_1111 = Scalar::MAX << (Scalar::BITS - 4);
 
     //  0 -> 0000_0000_..._0000
     //  1 -> 0000_0000_..._0001
@@ -58,7 +58,7 @@ pub fn sign(
     let fft_viwe = fft.as_view();
     let mut buffer = ComputationBuffers::new();
     let buffer_size_req =
-        cmux_assign_mem_optimized_requirement::<u64>(glwe_size, poly_size, fft_viwe)
+        cmux_assign_mem_optimized_requirement::<Scalar>(glwe_size, poly_size, fft_viwe)
             .unwrap()
             .unaligned_bytes_required();
 
@@ -115,11 +115,11 @@ pub fn sign(
     }
 }
 
-fn local_cmux(
-    input_0: &GlweCiphertext<Vec<u64>>,
-    input_1: &GlweCiphertext<Vec<u64>>,
+fn local_cmux<Scalar: UnsignedTorus>(
+    input_0: &GlweCiphertext<Vec<Scalar>>,
+    input_1: &GlweCiphertext<Vec<Scalar>>,
     control: &FourierGgswCiphertext<&[c64]>,
-    mut output: &mut GlweCiphertext<Vec<u64>>,
+    mut output: &mut GlweCiphertext<Vec<Scalar>>,
     buffer: &mut ComputationBuffers,
     fft: &Fft,
 ) {
@@ -130,11 +130,11 @@ fn local_cmux(
     cmux_assign_mem_optimized(&mut output, &mut temp, control, fft_view, stack);
 }
 
-fn plain_mux(
-    input_0: &GlweCiphertext<Vec<u64>>,
-    input_1: &GlweCiphertext<Vec<u64>>,
+fn plain_mux<Scalar: UnsignedTorus>(
+    input_0: &GlweCiphertext<Vec<Scalar>>,
+    input_1: &GlweCiphertext<Vec<Scalar>>,
     control: &usize,
-    output: &mut GlweCiphertext<Vec<u64>>,
+    output: &mut GlweCiphertext<Vec<Scalar>>,
 ) {
     match control {
         0 => {
@@ -7,6 +7,7 @@ use tfhe::core_crypto::{
 };
 
 use crate::{
+    error::ProcessorError,
     processors::{
         convert::convert_to_ggsw_after_blind_rotate_4_bit_rev_tr,
         lwe_stored_ksk::LweStoredReusedKeyswitchKey,
@@ -30,7 +31,8 @@ pub fn circuit_bootstrapping_4_bits_at_once_rev_tr<Scalar, InputCont>(
     ss_key: FourierGgswCiphertextListView,
     ksk: &LweStoredReusedKeyswitchKey<Vec<Scalar>>,
     parms: &ProcessorParam<Scalar>,
-) where
+) -> Result<(), ProcessorError>
+where
     Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize>,
     InputCont: Container<Element = Scalar>,
 {
@@ -93,8 +95,9 @@ pub fn circuit_bootstrapping_4_bits_at_once_rev_tr<Scalar, InputCont>(
             &auto_keys,
             ss_key,
             ciphertext_modulus,
-        );
+        )?;
         convert_standard_ggsw_ciphertext_to_fourier(&ggsw_temp, &mut fourier_ggsw);
     }
     // println!("ss time: {:.3?}", ss_start.elapsed());
+    Ok(())
 }
@@ -7,7 +7,7 @@ use tfhe::core_crypto::{fft_impl::fft64::c64, prelude::*};
 
 use tfhe::core_crypto::fft_impl::fft64::crypto::ggsw::FourierGgswCiphertextListView;
 
-use crate::processors::rev_trace::rev_trace_assign;
+use crate::{error::ProcessorError, processors::rev_trace::rev_trace_assign};
 
 pub fn convert_to_ggsw_after_blind_rotate_4_bit_rev_tr<Scalar, InputCont, OutputCont>(
     glev_in: &GlweCiphertextList<InputCont>,
@@ -16,7 +16,8 @@ pub fn convert_to_ggsw_after_blind_rotate_4_bit_rev_tr<Scalar, InputCont, Output
     auto_keys: &HashMap<usize, AutomorphKey<ABox<[c64]>>>,
     ss_key: FourierGgswCiphertextListView,
     ciphertext_modulus: CiphertextModulus<Scalar>,
-) where
+) -> Result<(), ProcessorError>
+where
     Scalar: UnsignedTorus,
     InputCont: Container<Element = Scalar>,
     OutputCont: ContainerMut<Element = Scalar>,
@@ -105,9 +106,10 @@ pub fn convert_to_ggsw_after_blind_rotate_4_bit_rev_tr<Scalar, InputCont, Output
         }
 
         convert_lwe_to_glwe_const(&buf_lwe, &mut glwe_out);
-        rev_trace_assign(&mut glwe_out, &auto_keys);
+        rev_trace_assign(&mut glwe_out, &auto_keys)?;
     }
     switch_scheme(&glev_out, ggsw_out, ss_key.as_view());
+    Ok(())
 }
 
 fn extract_and_adjust_lwe<Scalar, Cont>(
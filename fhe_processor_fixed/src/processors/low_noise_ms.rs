@@ -84,12 +84,16 @@ where
     (mask, body)
 }
 
-fn signed_div2<T: UnsignedTorus + CastInto<usize> + CastFrom<usize>>(x: T) -> T {
-    let x_unsigned: usize = x.cast_into();
-    let x_signed: i64 = x_unsigned.cast_into();
-    let half = x_signed / 2;
-    let out: usize = half.cast_into();
-    T::cast_from(out)
+/// `x / 2` on the two's complement reading of `x`, rounded towards zero.
+///
+/// Stays within `T`, going through `i64` would lose the sign of a `u32` and the high bits of
+/// a `u128`.
+fn signed_div2<T: UnsignedTorus>(x: T) -> T {
+    if x >> (T::BITS - 1) == T::ONE {
+        (x.wrapping_neg() >> 1).wrapping_neg()
+    } else {
+        x >> 1
+    }
 }
 
 #[cfg(test)]
@@ -5,12 +5,15 @@ use refined_tfhe_lhe::{glwe_ciphertext_clone_from, glwe_ciphertext_mod_raise_fro
 use concrete_fft::c64;
 use tfhe::core_crypto::prelude::{glwe_ciphertext_add_assign, CiphertextModulus, ContainerMut, GlweCiphertext, GlweCiphertextOwned, UnsignedInteger, UnsignedTorus};
 
+use crate::error::ProcessorError;
+
 
 
 pub fn rev_trace_assign<Scalar, Cont>(
     input: &mut GlweCiphertext<Cont>,
     auto_keys: &HashMap<usize, AutomorphKey<ABox<[c64]>>>,
-) where
+) -> Result<(), ProcessorError>
+where
     Scalar: UnsignedTorus,
     Cont: ContainerMut<Element=Scalar>,
 {
@@ -25,13 +28,16 @@ pub fn rev_trace_assign<Scalar, Cont>(
     let log_polynomial_size = polynomial_size.0.ilog2() as usize;
     for i in (1..=(log_polynomial_size)).rev() {
         let k = polynomial_size.0 / (1 << (i - 1)) + 1;
-        let auto_key = auto_keys.get(&k).unwrap();
+        let auto_key = auto_keys
+            .get(&k)
+            .ok_or(ProcessorError::MissingAutomorphismKey(k))?;
         mod_switch_one_bit_then_rise_back(&mut out);
         auto_key.auto(&mut buf, &out);
         glwe_ciphertext_add_assign(&mut out, &buf);
     }
 
     glwe_ciphertext_clone_from(input, &out);
+    Ok(())
 }
 
 fn mod_switch_one_bit_then_rise_back<Scalar, Cont>(
@@ -0,0 +1,313 @@
+use crate::{
+    error::ProcessorError,
+    operations::{manager::Step, operand::ArithmeticOp, operation::OperandType},
+};
+
+/// A program written in the text assembly of the processor.
+///
+/// Every line holds one instruction `rD = op a, b` where the operands are registers `rN`
+/// (memory slots) or immediates `#N` (decimal or `0x` hexadecimal). `mov rA` and `not rA` take
+/// one register, `csel rC, rA, rB` gives `rA` if the condition `rC` is non-zero, else `rB`. The
+/// fused `minmax` and `divmod` write two registers from one step. Text after `;` is a comment.
+///
+/// ```text
+/// r5 = max r0, r1
+/// r2 = add r2, #7   ; cipher-plain
+/// r1 = csel r0, r3, r4
+/// r0, r1 = minmax r0, r1
+/// ```
+pub struct AssemblyProgram {
+    pub operations: Vec<(ArithmeticOp, OperandType, Option<usize>)>,
+    pub steps: Vec<Step>,
+}
+
+enum Operand {
+    Register(usize),
+    Immediate(usize),
+}
+
+impl AssemblyProgram {
+    /// Parse `source`, registering each distinct `(op, operand type, immediate)` once.
+    ///
+    /// # Errors
+    ///
+    /// [`ProcessorError::Syntax`] with the 1-based number of the first malformed line.
+    pub fn parse(source: &str) -> Result<Self, ProcessorError> {
+        let mut program = Self {
+            operations: Vec::new(),
+            steps: Vec::new(),
+        };
+        for (number, line) in source.lines().enumerate() {
+            let code = line.split(';').next().unwrap_or_default().trim();
+            if code.is_empty() {
+                continue;
+            }
+            program
+                .parse_instruction(code)
+                .map_err(|message| ProcessorError::Syntax {
+                    line: number + 1,
+                    message,
+                })?;
+        }
+        Ok(program)
+    }
+
+    pub fn load_operatonis(&self) -> Vec<(ArithmeticOp, OperandType, Option<usize>)> {
+        self.operations.clone()
+    }
+
+    pub fn load_programs(&self) -> Vec<Step> {
+        self.steps.clone()
+    }
+
+    /// Number of memory slots needed by the program, i.e. the highest register plus one.
+    pub fn memory_size(&self) -> usize {
+        self.steps
+            .iter()
+            .flat_map(|step| {
+                let outputs = step.outputs().map(|(_, output)| output);
+                step.input_indices.iter().copied().chain(outputs)
+            })
+            .max()
+            .map_or(0, |register| register + 1)
+    }
+
+    fn parse_instruction(&mut self, code: &str) -> Result<(), String> {
+        let (dest, expr) = code
+            .split_once('=')
+            .ok_or_else(|| format!("expected `rD = op operands`, found `{code}`"))?;
+        let output_indices = dest
+            .split(',')
+            .map(|dest| match parse_operand(dest.trim())? {
+                Operand::Register(register) => Ok(register),
+                Operand::Immediate(_) => Err("the destination must be a register".to_string()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let expr = expr.trim();
+        let (mnemonic, operands) = expr.split_once(char::is_whitespace).unwrap_or((expr, ""));
+        let ops = match mnemonic.to_ascii_lowercase().as_str() {
+            "minmax" => vec![ArithmeticOp::MAX, ArithmeticOp::MIN],
+            "divmod" => vec![ArithmeticOp::Div, ArithmeticOp::Mod],
+            _ => vec![parse_mnemonic(mnemonic)?],
+        };
+        if ops.len() != output_indices.len() {
+            return Err(format!(
+                "`{mnemonic}` writes {} register(s), found {}",
+                ops.len(),
+                output_indices.len()
+            ));
+        }
+        let op = ops[0];
+        let operands = operands
+            .split(',')
+            .map(str::trim)
+            .filter(|operand| !operand.is_empty())
+            .map(parse_operand)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (op_type, immediate, input_indices) = match (op, operands.as_slice()) {
+            (ArithmeticOp::MOVE, [Operand::Register(a)]) => {
+                (OperandType::BothCipher, None, vec![*a])
+            }
+            (ArithmeticOp::NOT, [Operand::Register(a)]) => {
+                (OperandType::CipherPlain, Some(0), vec![*a])
+            }
+            (
+                ArithmeticOp::CSEL,
+                [Operand::Register(c), Operand::Register(a), Operand::Register(b)],
+            ) => (OperandType::BothCipher, None, vec![*c, *a, *b]),
+            (ArithmeticOp::MOVE | ArithmeticOp::NOT | ArithmeticOp::CSEL, _) => {
+                return Err(format!("wrong operands for `{mnemonic}`"));
+            }
+            (_, [Operand::Register(a), Operand::Register(b)]) => {
+                (OperandType::BothCipher, None, vec![*a, *b])
+            }
+            (_, [Operand::Register(a), Operand::Immediate(imm)]) => {
+                (OperandType::CipherPlain, Some(*imm), vec![*a])
+            }
+            (_, [Operand::Immediate(imm), Operand::Register(a)]) => {
+                (OperandType::PlainCipher, Some(*imm), vec![*a])
+            }
+            (_, [Operand::Immediate(_), Operand::Immediate(_)]) => {
+                return Err("at least one operand must be a register".to_string());
+            }
+            (_, operands) => {
+                return Err(format!(
+                    "`{mnemonic}` takes 2 operands, found {}",
+                    operands.len()
+                ));
+            }
+        };
+
+        let op_index = self.intern((op, op_type, immediate));
+        let mut step = Step::new(op_index, input_indices, output_indices[0]);
+        for (&op, &output_index) in ops.iter().zip(&output_indices).skip(1) {
+            step = step.with_output(self.intern((op, op_type, immediate)), output_index);
+        }
+        self.steps.push(step);
+        Ok(())
+    }
+
+    fn intern(&mut self, entry: (ArithmeticOp, OperandType, Option<usize>)) -> usize {
+        match self.operations.iter().position(|known| *known == entry) {
+            Some(index) => index,
+            None => {
+                self.operations.push(entry);
+                self.operations.len() - 1
+            }
+        }
+    }
+}
+
+fn parse_mnemonic(mnemonic: &str) -> Result<ArithmeticOp, String> {
+    let op = match mnemonic.to_ascii_lowercase().as_str() {
+        "add" => ArithmeticOp::Add,
+        "sub" => ArithmeticOp::Sub,
+        "mul" => ArithmeticOp::Mul,
+        "mulh" => ArithmeticOp::Mulh,
+        "div" => ArithmeticOp::Div,
+        "mod" => ArithmeticOp::Mod,
+        "eq" => ArithmeticOp::EQ,
+        "gt" => ArithmeticOp::GT,
+        "lt" => ArithmeticOp::LT,
+        "gte" => ArithmeticOp::GTE,
+        "lte" => ArithmeticOp::LTE,
+        "max" => ArithmeticOp::MAX,
+        "min" => ArithmeticOp::MIN,
+        "rl" => ArithmeticOp::RL,
+        "rr" => ArithmeticOp::RR,
+        "sl" => ArithmeticOp::SL,
+        "sr" => ArithmeticOp::SR,
+        "or" => ArithmeticOp::OR,
+        "and" => ArithmeticOp::AND,
+        "xor" => ArithmeticOp::XOR,
+        "nand" => ArithmeticOp::NAND,
+        "not" => ArithmeticOp::NOT,
+        "mov" | "move" => ArithmeticOp::MOVE,
+        "csel" => ArithmeticOp::CSEL,
+        "gteo" => ArithmeticOp::GTEO,
+        "sign" => ArithmeticOp::SIGN,
+        _ => return Err(format!("unknown operation `{mnemonic}`")),
+    };
+    Ok(op)
+}
+
+fn parse_operand(operand: &str) -> Result<Operand, String> {
+    let parse_number = |digits: &str| match digits.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => digits.parse(),
+    };
+    if let Some(register) = operand.strip_prefix('r') {
+        parse_number(register)
+            .map(Operand::Register)
+            .map_err(|_| format!("invalid register `{operand}`"))
+    } else if let Some(immediate) = operand.strip_prefix('#') {
+        parse_number(immediate)
+            .map(Operand::Immediate)
+            .map_err(|_| format!("invalid immediate `{operand}`"))
+    } else {
+        Err(format!(
+            "expected a register `rN` or an immediate `#N`, found `{operand}`"
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::programs::bubble::BubbleProgram;
+
+    #[test]
+    fn test_assembly_parse() {
+        let program = AssemblyProgram::parse(
+            "r5 = max r0, r1\n\
+             \n\
+             r2 = add r2, #7 ; increment\n\
+             r3 = sub #0x10, r2\n\
+             r1 = csel r0, r3, r4\n\
+             r4 = mov r5\n\
+             r6 = MAX r2, r3\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            program.operations,
+            vec![
+                (ArithmeticOp::MAX, OperandType::BothCipher, None),
+                (ArithmeticOp::Add, OperandType::CipherPlain, Some(7)),
+                (ArithmeticOp::Sub, OperandType::PlainCipher, Some(16)),
+                (ArithmeticOp::CSEL, OperandType::BothCipher, None),
+                (ArithmeticOp::MOVE, OperandType::BothCipher, None),
+            ]
+        );
+        assert_eq!(
+            program.steps,
+            vec![
+                Step::new(0, vec![0, 1], 5),
+                Step::new(1, vec![2], 2),
+                Step::new(2, vec![2], 3),
+                Step::new(3, vec![0, 3, 4], 1),
+                Step::new(4, vec![5], 4),
+                Step::new(0, vec![2, 3], 6),
+            ]
+        );
+        assert_eq!(program.memory_size(), 7);
+    }
+
+    #[test]
+    fn test_assembly_matches_bubble_program() {
+        let program = AssemblyProgram::parse(
+            "r2 = max r0, r1\n\
+             r0 = min r0, r1\n\
+             r1 = mov r2\n",
+        )
+        .unwrap();
+        assert_eq!(program.operations, BubbleProgram::load_operatonis());
+        assert_eq!(program.steps, BubbleProgram::load_programs(2));
+    }
+
+    #[test]
+    fn test_assembly_fused() {
+        let program = AssemblyProgram::parse(
+            "r0, r1 = minmax r0, r1\n\
+             r2, r3 = divmod r0, #3\n",
+        )
+        .unwrap();
+        assert_eq!(
+            program.operations,
+            vec![
+                (ArithmeticOp::MAX, OperandType::BothCipher, None),
+                (ArithmeticOp::MIN, OperandType::BothCipher, None),
+                (ArithmeticOp::Div, OperandType::CipherPlain, Some(3)),
+                (ArithmeticOp::Mod, OperandType::CipherPlain, Some(3)),
+            ]
+        );
+        assert_eq!(
+            program.steps,
+            vec![
+                Step::new(0, vec![0, 1], 0).with_output(1, 1),
+                Step::new(2, vec![0], 2).with_output(3, 3),
+            ]
+        );
+        assert_eq!(program.memory_size(), 4);
+    }
+
+    #[test]
+    fn test_assembly_errors() {
+        let error = |source: &str| match AssemblyProgram::parse(source) {
+            Err(ProcessorError::Syntax { line, .. }) => line,
+            _ => panic!("`{source}` should not parse"),
+        };
+        assert_eq!(error("r0 = add r0, r1\nr1 = foo r0, r1"), 2);
+        assert_eq!(error("; header\n\nr1 add r0, r1"), 3);
+        assert_eq!(error("#1 = add r0, r1"), 1);
+        assert_eq!(error("r1 = add #1, #2"), 1);
+        assert_eq!(error("r1 = add r0"), 1);
+        assert_eq!(error("r1 = csel r0, r2"), 1);
+        assert_eq!(error("r1 = add r0, x2"), 1);
+        assert_eq!(error("r1 = add r0, #-1"), 1);
+        assert_eq!(error("r1 = minmax r0, r2"), 1);
+        assert_eq!(error("r1, r2 = add r0, r2"), 1);
+    }
+}
@@ -1,4 +1,9 @@
-use crate::operations::{manager::Step, operand::ArithmeticOp, operation::OperandType};
+use crate::operations::{
+    control::{PlanNode, Slot, StepTemplate},
+    manager::Step,
+    operand::ArithmeticOp,
+    operation::OperandType,
+};
 
 pub struct BubbleProgram;
 
@@ -21,4 +26,29 @@ impl BubbleProgram {
         }
         programs
     }
+    /// Same sort with one fused MIN/MAX step per swap, so `buf[size]` is not used.
+    pub fn load_fused_programs(size: usize) -> Vec<Step> {
+        let mut programs = Vec::new();
+        for i in (0..size).rev() {
+            for j in 0..i {
+                // buf[j], buf[j+1] = min(buf[j], buf[j+1]), max(buf[j], buf[j+1])
+                programs.push(Step::new(1, vec![j, j + 1], j).with_output(0, j + 1));
+            }
+        }
+        programs
+    }
+    /// Oblivious form of [`BubbleProgram::load_fused_programs`]: `size - 1` full passes of
+    /// fused MIN/MAX steps.
+    pub fn load_repeat_programs(size: usize) -> Vec<PlanNode> {
+        let passes = size.saturating_sub(1);
+        let swap = StepTemplate::new(1, vec![Slot::at(1, 0), Slot::at(1, 1)], Slot::at(1, 0))
+            .with_output(0, Slot::at(1, 1));
+        vec![PlanNode::Repeat {
+            count: passes,
+            body: vec![PlanNode::Repeat {
+                count: passes,
+                body: vec![PlanNode::Step(swap)],
+            }],
+        }]
+    }
 }
@@ -0,0 +1,431 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::operations::{manager::Step, operand::ArithmeticOp, operation::OperandType};
+
+/// A value of a [`ProgramBuilder`] program. Memory slots are only assigned by
+/// [`ProgramBuilder::build`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Register(usize);
+
+struct Instruction {
+    inputs: Vec<Register>,
+    // (op_index, output) pairs, more than one for a fused step
+    outputs: Vec<(usize, Register)>,
+}
+
+/// Builds an [`OperationManager`](crate::operations::manager::OperationManager) program over
+/// symbolic registers.
+///
+/// ```ignore
+/// let mut prog = ProgramBuilder::new();
+/// let (a, b, c) = (prog.input(), prog.input(), prog.input());
+/// let s = prog.add(a, b);
+/// let m = prog.max(s, c);
+/// prog.output(m);
+/// let program = prog.build();
+/// ```
+#[derive(Default)]
+pub struct ProgramBuilder {
+    operations: Vec<(ArithmeticOp, OperandType, Option<usize>)>,
+    op_indices: HashMap<(ArithmeticOp, OperandType, Option<usize>), usize>,
+    instructions: Vec<Instruction>,
+    inputs: Vec<Register>,
+    outputs: Vec<Register>,
+    register_count: usize,
+}
+
+/// The result of [`ProgramBuilder::build`].
+pub struct BuiltProgram {
+    pub operations: Vec<(ArithmeticOp, OperandType, Option<usize>)>,
+    pub steps: Vec<Step>,
+    /// Memory slot of each input, in the order of [`ProgramBuilder::input`].
+    pub input_slots: Vec<usize>,
+    /// Memory slot of each output, in the order of [`ProgramBuilder::output`].
+    pub output_slots: Vec<usize>,
+    pub memory_size: usize,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A value loaded before execution. The `i`-th input is stored in slot `i`.
+    pub fn input(&mut self) -> Register {
+        let register = self.new_register();
+        self.inputs.push(register);
+        register
+    }
+
+    /// Keep `register` in memory after execution.
+    pub fn output(&mut self, register: Register) {
+        self.outputs.push(register);
+    }
+
+    /// `op(a, b)` on two ciphertexts.
+    pub fn binary(&mut self, op: ArithmeticOp, a: Register, b: Register) -> Register {
+        self.push(op, OperandType::BothCipher, None, vec![a, b])
+    }
+
+    /// `op(a, immediate)`.
+    pub fn cipher_plain(&mut self, op: ArithmeticOp, a: Register, immediate: usize) -> Register {
+        self.push(op, OperandType::CipherPlain, Some(immediate), vec![a])
+    }
+
+    /// `op(immediate, a)`.
+    pub fn plain_cipher(&mut self, op: ArithmeticOp, immediate: usize, a: Register) -> Register {
+        self.push(op, OperandType::PlainCipher, Some(immediate), vec![a])
+    }
+
+    pub fn add(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::Add, a, b)
+    }
+
+    pub fn sub(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::Sub, a, b)
+    }
+
+    pub fn mul(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::Mul, a, b)
+    }
+
+    pub fn mulh(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::Mulh, a, b)
+    }
+
+    pub fn div(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::Div, a, b)
+    }
+
+    pub fn modulo(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::Mod, a, b)
+    }
+
+    pub fn eq(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::EQ, a, b)
+    }
+
+    pub fn gt(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::GT, a, b)
+    }
+
+    pub fn lt(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::LT, a, b)
+    }
+
+    pub fn gte(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::GTE, a, b)
+    }
+
+    pub fn lte(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::LTE, a, b)
+    }
+
+    pub fn max(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::MAX, a, b)
+    }
+
+    pub fn min(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::MIN, a, b)
+    }
+
+    pub fn rl(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::RL, a, b)
+    }
+
+    pub fn rr(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::RR, a, b)
+    }
+
+    pub fn sl(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::SL, a, b)
+    }
+
+    pub fn sr(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::SR, a, b)
+    }
+
+    pub fn or(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::OR, a, b)
+    }
+
+    pub fn and(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::AND, a, b)
+    }
+
+    pub fn xor(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::XOR, a, b)
+    }
+
+    pub fn nand(&mut self, a: Register, b: Register) -> Register {
+        self.binary(ArithmeticOp::NAND, a, b)
+    }
+
+    pub fn not(&mut self, a: Register) -> Register {
+        self.cipher_plain(ArithmeticOp::NOT, a, 0)
+    }
+
+    pub fn sign(&mut self, a: Register, immediate: usize) -> Register {
+        self.cipher_plain(ArithmeticOp::SIGN, a, immediate)
+    }
+
+    /// `(max(a, b), min(a, b))` from one step.
+    pub fn minmax(&mut self, a: Register, b: Register) -> (Register, Register) {
+        let outputs = self.fused(&[ArithmeticOp::MAX, ArithmeticOp::MIN], a, b);
+        (outputs[0], outputs[1])
+    }
+
+    /// `(a / b, a % b)` from one step.
+    pub fn divmod(&mut self, a: Register, b: Register) -> (Register, Register) {
+        let outputs = self.fused(&[ArithmeticOp::Div, ArithmeticOp::Mod], a, b);
+        (outputs[0], outputs[1])
+    }
+
+    /// `op(a, b)` for every op of `ops`, sharing the circuit bootstrapping of `a` and `b`.
+    pub fn fused(&mut self, ops: &[ArithmeticOp], a: Register, b: Register) -> Vec<Register> {
+        assert!(!ops.is_empty(), "a fused step needs at least one operation");
+        self.push_fused(ops, OperandType::BothCipher, None, vec![a, b])
+    }
+
+    /// A copy of `a`.
+    pub fn mov(&mut self, a: Register) -> Register {
+        self.push(ArithmeticOp::MOVE, OperandType::BothCipher, None, vec![a])
+    }
+
+    /// `a` if `condition` is non-zero, else `b`.
+    pub fn csel(&mut self, condition: Register, a: Register, b: Register) -> Register {
+        self.push(
+            ArithmeticOp::CSEL,
+            OperandType::BothCipher,
+            None,
+            vec![condition, a, b],
+        )
+    }
+
+    /// Assign memory slots and emit the plan.
+    ///
+    /// Inputs take the first slots. Every other register takes the lowest slot free at its
+    /// definition: a slot is freed after the last read of its register, so a step may write
+    /// over one of its own inputs. Outputs are never freed.
+    pub fn build(self) -> BuiltProgram {
+        const LIVE_OUT: usize = usize::MAX;
+
+        let mut last_use = vec![None; self.register_count];
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            for input in &instruction.inputs {
+                last_use[input.0] = Some(index);
+            }
+        }
+        for output in &self.outputs {
+            last_use[output.0] = Some(LIVE_OUT);
+        }
+
+        let mut slots = vec![0; self.register_count];
+        let mut free = BTreeSet::new();
+        for (slot, input) in self.inputs.iter().enumerate() {
+            slots[input.0] = slot;
+            if last_use[input.0].is_none() {
+                free.insert(slot);
+            }
+        }
+        let mut memory_size = self.inputs.len();
+
+        let mut steps = Vec::with_capacity(self.instructions.len());
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let input_indices: Vec<usize> = instruction
+                .inputs
+                .iter()
+                .map(|input| slots[input.0])
+                .collect();
+            for input in &instruction.inputs {
+                if last_use[input.0] == Some(index) {
+                    free.insert(slots[input.0]);
+                }
+            }
+
+            let mut outputs = Vec::with_capacity(instruction.outputs.len());
+            for &(op_index, output) in &instruction.outputs {
+                let output_index = free.pop_first().unwrap_or_else(|| {
+                    memory_size += 1;
+                    memory_size - 1
+                });
+                slots[output.0] = output_index;
+                outputs.push((op_index, output_index));
+            }
+            // dead outputs still take distinct slots within the step
+            for &(_, output) in &instruction.outputs {
+                if last_use[output.0].is_none() {
+                    free.insert(slots[output.0]);
+                }
+            }
+
+            let (op_index, output_index) = outputs[0];
+            let mut step = Step::new(op_index, input_indices, output_index);
+            for &(op_index, output_index) in &outputs[1..] {
+                step = step.with_output(op_index, output_index);
+            }
+            steps.push(step);
+        }
+
+        BuiltProgram {
+            operations: self.operations,
+            steps,
+            input_slots: self.inputs.iter().map(|input| slots[input.0]).collect(),
+            output_slots: self.outputs.iter().map(|output| slots[output.0]).collect(),
+            memory_size,
+        }
+    }
+
+    fn new_register(&mut self) -> Register {
+        self.register_count += 1;
+        Register(self.register_count - 1)
+    }
+
+    fn push(
+        &mut self,
+        op: ArithmeticOp,
+        op_type: OperandType,
+        immediate: Option<usize>,
+        inputs: Vec<Register>,
+    ) -> Register {
+        self.push_fused(&[op], op_type, immediate, inputs)[0]
+    }
+
+    fn push_fused(
+        &mut self,
+        ops: &[ArithmeticOp],
+        op_type: OperandType,
+        immediate: Option<usize>,
+        inputs: Vec<Register>,
+    ) -> Vec<Register> {
+        let mut outputs = Vec::with_capacity(ops.len());
+        for &op in ops {
+            let operations = &mut self.operations;
+            let op_index = *self
+                .op_indices
+                .entry((op, op_type, immediate))
+                .or_insert_with(|| {
+                    operations.push((op, op_type, immediate));
+                    operations.len() - 1
+                });
+            outputs.push((op_index, self.new_register()));
+        }
+        let registers = outputs.iter().map(|&(_, output)| output).collect();
+        self.instructions.push(Instruction { inputs, outputs });
+        registers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `program` on plaintext bytes.
+    fn run_plain(program: &BuiltProgram, inputs: &[u8]) -> Vec<u8> {
+        let mut memory = vec![0u8; program.memory_size];
+        for (&slot, &value) in program.input_slots.iter().zip(inputs) {
+            memory[slot] = value;
+        }
+        for step in &program.steps {
+            let a = memory[step.input_indices[0]];
+            let outputs: Vec<_> = step
+                .outputs()
+                .map(|(op_index, output_index)| {
+                    let (op, op_type, immediate) = program.operations[op_index];
+                    let value = match (op, op_type) {
+                        (ArithmeticOp::MOVE, _) => a,
+                        (_, OperandType::BothCipher) => {
+                            op.compute(a, memory[step.input_indices[1]])
+                        }
+                        (_, OperandType::CipherPlain) => op.compute(a, immediate.unwrap() as u8),
+                        (_, OperandType::PlainCipher) => op.compute(immediate.unwrap() as u8, a),
+                    };
+                    (output_index, value)
+                })
+                .collect();
+            for (output_index, value) in outputs {
+                memory[output_index] = value;
+            }
+        }
+        program
+            .output_slots
+            .iter()
+            .map(|&slot| memory[slot])
+            .collect()
+    }
+
+    #[test]
+    fn test_builder_squaresum() {
+        let size = 6;
+        let mut prog = ProgramBuilder::new();
+        let inputs: Vec<Register> = (0..size).map(|_| prog.input()).collect();
+        let mut acc = prog.mul(inputs[0], inputs[0]);
+        for &x in &inputs[1..] {
+            let square = prog.mul(x, x);
+            acc = prog.add(acc, square);
+        }
+        prog.output(acc);
+        let program = prog.build();
+
+        assert_eq!(
+            program.operations,
+            vec![
+                (ArithmeticOp::Mul, OperandType::BothCipher, None),
+                (ArithmeticOp::Add, OperandType::BothCipher, None),
+            ]
+        );
+        assert_eq!(program.input_slots, (0..size).collect::<Vec<_>>());
+        // every input is dead after its square, so no slot is added
+        assert_eq!(program.memory_size, size);
+
+        let values = [3u8, 1, 4, 1, 5, 9];
+        let expected = values
+            .iter()
+            .fold(0u8, |acc, x| acc.wrapping_add(x.wrapping_mul(*x)));
+        assert_eq!(run_plain(&program, &values), vec![expected]);
+    }
+
+    #[test]
+    fn test_builder_keeps_live_values() {
+        let mut prog = ProgramBuilder::new();
+        let a = prog.input();
+        let b = prog.input();
+        let c = prog.input();
+        let s = prog.add(a, b);
+        let d = prog.cipher_plain(ArithmeticOp::Sub, a, 7);
+        let m = prog.max(s, c);
+        let n = prog.plain_cipher(ArithmeticOp::Sub, 200, d);
+        let t = prog.mov(m);
+        let _unused = prog.xor(t, n);
+        prog.output(m);
+        prog.output(n);
+        prog.output(a);
+        let program = prog.build();
+
+        assert_eq!(program.operations.len(), 6);
+        assert_eq!(program.output_slots[2], 0);
+        assert_eq!(
+            run_plain(&program, &[20, 30, 40]),
+            vec![50, 200u8.wrapping_sub(13), 20]
+        );
+    }
+    #[test]
+    fn test_builder_fused() {
+        let mut prog = ProgramBuilder::new();
+        let inputs: Vec<Register> = (0..3).map(|_| prog.input()).collect();
+        // sorting network on three values
+        let (hi, lo) = prog.minmax(inputs[0], inputs[1]);
+        let (hi, mid) = prog.minmax(hi, inputs[2]);
+        let (mid, lo) = prog.minmax(mid, lo);
+        let (q, r) = prog.divmod(hi, lo);
+        for register in [lo, mid, hi, q, r] {
+            prog.output(register);
+        }
+        let program = prog.build();
+
+        assert_eq!(program.operations.len(), 4);
+        assert_eq!(program.steps.len(), 4);
+        assert!(program.steps.iter().all(|step| step.fused.len() == 1));
+        assert_eq!(run_plain(&program, &[7, 30, 4]), vec![4, 7, 30, 7, 2]);
+    }
+}
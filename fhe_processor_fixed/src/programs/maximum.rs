@@ -1,4 +1,9 @@
-use crate::operations::{manager::Step, operand::ArithmeticOp, operation::OperandType};
+use crate::operations::{
+    control::{PlanNode, Slot, StepTemplate},
+    manager::Step,
+    operand::ArithmeticOp,
+    operation::OperandType,
+};
 
 pub struct MaximumProgram;
 
@@ -15,4 +20,17 @@ impl MaximumProgram {
         }
         programs
     }
+    /// [`MaximumProgram::load_programs`] as a single repeated step.
+    pub fn load_repeat_programs(size: usize) -> Vec<PlanNode> {
+        // buf[size] = max(buf[i], buf[size])
+        let step = StepTemplate::new(
+            0,
+            vec![Slot::at(0, 0), Slot::Fixed(size)],
+            Slot::Fixed(size),
+        );
+        vec![PlanNode::Repeat {
+            count: size,
+            body: vec![PlanNode::Step(step)],
+        }]
+    }
 }
@@ -1,4 +1,6 @@
 pub mod maximum;
 pub mod bubble;
 pub mod squaresum;
-pub mod average;
\ No newline at end of file
+pub mod average;
+pub mod assembly;
+pub mod builder;
\ No newline at end of file
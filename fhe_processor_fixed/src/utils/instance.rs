@@ -3,9 +3,12 @@ use lazy_static::lazy_static;
 use refined_tfhe_lhe::FftType;
 use tfhe::core_crypto::prelude::*;
 
-lazy_static! {
-
-    pub static ref SetI: ProcessorParam<u64> = ProcessorParam::new(
+/// Parameter set I over a native `u64` torus, see [`set_i_u32`] for `u32`.
+///
+/// The noise and the split FFT are tuned for `u64`, there is no `u128` set until one is tuned
+/// for the 128-bit FFT.
+pub fn set_i() -> ProcessorParam<u64> {
+    ProcessorParam::new(
         LweDimension(710), // lwe_dimension
         StandardDev(1.525878906e-5), // lwe_modular_std_dev
         PolynomialSize(1024), // polynomial_size
@@ -26,9 +29,12 @@ lazy_static! {
         CiphertextModulus::<u64>::new_native(), // ciphertext_modulus
         4, // message_size
         4,
-    );
+    )
+}
 
-        pub static ref SetII: ProcessorParam<u64> = ProcessorParam::new(
+/// Parameter set II over a native `u64` torus.
+pub fn set_ii() -> ProcessorParam<u64> {
+    ProcessorParam::new(
         LweDimension(710), // lwe_dimension
         StandardDev(1.525878906e-5), // lwe_modular_std_dev
         PolynomialSize(1024), // polynomial_size
@@ -49,7 +55,44 @@ lazy_static! {
         CiphertextModulus::<u64>::new_native(), // ciphertext_modulus
         4, // message_size
         4,
-    );
+    )
+}
+
+/// Parameter set for 8-bit operations over a `u32` torus.
+///
+/// Same key sizes as [`set_i`], but the GLWE noise of [`set_i`] is far below the precision of
+/// `u32`, so it is raised to 2^-30 and every decomposition is kept within 24 bits.
+pub fn set_i_u32() -> ProcessorParam<u32> {
+    ProcessorParam::new(
+        LweDimension(710), // lwe_dimension
+        StandardDev(1.525878906e-5), // lwe_modular_std_dev
+        PolynomialSize(1024), // polynomial_size
+        GlweDimension(2), // glwe_dimension
+        StandardDev(9.313225746e-10), // glwe_modular_std_dev
+        DecompositionBaseLog(6), // pbs_base_log
+        DecompositionLevelCount(4), // pbs_level
+        DecompositionBaseLog(4), // ks_base_log
+        DecompositionLevelCount(4), // ks_level
+        DecompositionBaseLog(6), // auto_base_log
+        DecompositionLevelCount(4), // auto_level
+        FftType::Vanilla, // fft_type_auto
+        DecompositionBaseLog(6), // ss_base_log
+        DecompositionLevelCount(4), // ss_level
+        DecompositionBaseLog(6), // cbs_base_log
+        DecompositionLevelCount(2), // cbs_level
+        LutCountLog(1), // log_lut_count
+        CiphertextModulus::<u32>::new_native(), // ciphertext_modulus
+        4, // message_size
+        4,
+    )
+}
+
+lazy_static! {
+
+    pub static ref SetI32: ProcessorParam<u32> = set_i_u32();
+
+    pub static ref SetI: ProcessorParam<u64> = set_i();
 
+    pub static ref SetII: ProcessorParam<u64> = set_ii();
 
 }
@@ -0,0 +1,484 @@
+//! Table lookup on large-precision inputs: the input GLWE is split into digits, every digit is
+//! circuit-bootstrapped into GGSW bits and the bits select an entry of the table by vertical
+//! packing.
+use std::collections::HashMap;
+
+use aligned_vec::ABox;
+use concrete_fft::c64;
+use fhe_processor::{
+    error::ProcessorError,
+    operations::{
+        cipher_lut::generate_lut_from_vecs_auto, manager::concat_ggsw_lists,
+        operation::horizontal_vertical_packing_without_extract,
+        plain_lut::split_adjusted_lut_by_chunk,
+    },
+    processors::{
+        cbs_4_bits::circuit_bootstrapping_4_bits_at_once_rev_tr,
+        key_gen::allocate_and_generate_new_reused_lwe_key,
+        lwe_stored_ksk::{
+            LweStoredReusedKeyswitchKey, allocate_and_generate_new_stored_reused_lwe_keyswitch_key,
+        },
+    },
+    utils::parms::ProcessorParam,
+};
+use rayon::{
+    iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator},
+    slice::ParallelSliceMut,
+};
+use refined_tfhe_lhe::{AutomorphKey, gen_all_auto_keys, generate_scheme_switching_key};
+use tfhe::core_crypto::{
+    fft_impl::fft64::crypto::wop_pbs::vertical_packing_scratch,
+    prelude::{
+        ActivatedRandomGenerator, Cleartext, ComputationBuffers, Container,
+        ContiguousEntityContainer, EncryptionRandomGenerator, Fft, FourierGgswCiphertextList,
+        FourierLweBootstrapKey, FourierLweBootstrapKeyOwned, GlweCiphertext, GlweCiphertextList,
+        GlweSecretKey, LweCiphertext, LweSize, MonomialDegree, PolynomialList,
+        allocate_and_generate_new_lwe_bootstrap_key, convert_standard_lwe_bootstrap_key_to_fourier,
+        extract_lwe_sample_from_glwe_ciphertext, lwe_ciphertext_cleartext_mul_assign,
+    },
+};
+
+use crate::algorithms::{
+    decomposer::DigitDecomposer,
+    digit_decompose::{DigitMode, DigitOrder},
+};
+
+/// Evaluation keys of the circuit bootstrapping and of the vertical packing.
+///
+/// The keys are generated from the GLWE key of the digits, reinterpreted with the polynomial
+/// size of `params`, so that the digits produced by a [`DigitDecomposer`] can be
+/// circuit-bootstrapped directly.
+pub struct CbsKeys {
+    params: ProcessorParam<u64>,
+    ksk: LweStoredReusedKeyswitchKey<Vec<u64>>,
+    fourier_bsk: FourierLweBootstrapKeyOwned,
+    auto_keys: HashMap<usize, AutomorphKey<ABox<[c64]>>>,
+    ss_key: FourierGgswCiphertextList<Vec<c64>>,
+    fft: Fft,
+}
+
+impl CbsKeys {
+    pub fn new<KeyCont>(
+        glwe_key: &GlweSecretKey<KeyCont>,
+        params: ProcessorParam<u64>,
+        encryption_generator: &mut EncryptionRandomGenerator<ActivatedRandomGenerator>,
+    ) -> Self
+    where
+        KeyCont: Container<Element = u64>,
+    {
+        let polynomial_size = params.polynomial_size();
+        let glwe_lwe_sk = glwe_key.as_lwe_secret_key();
+        let cbs_glwe_key =
+            GlweSecretKey::from_container(glwe_key.as_ref().to_vec(), polynomial_size);
+        let lwe_sk_after_ks =
+            allocate_and_generate_new_reused_lwe_key(&glwe_lwe_sk, params.lwe_dimension());
+        let ksk = allocate_and_generate_new_stored_reused_lwe_keyswitch_key(
+            &glwe_lwe_sk,
+            &lwe_sk_after_ks,
+            params.ks_base_log(),
+            params.ks_level(),
+            params.lwe_modular_std_dev(),
+            params.ciphertext_modulus(),
+            encryption_generator,
+        );
+
+        let bsk = allocate_and_generate_new_lwe_bootstrap_key(
+            &lwe_sk_after_ks,
+            &cbs_glwe_key,
+            params.pbs_base_log(),
+            params.pbs_level(),
+            params.glwe_modular_std_dev(),
+            params.ciphertext_modulus(),
+            encryption_generator,
+        );
+        let mut fourier_bsk = FourierLweBootstrapKey::new(
+            bsk.input_lwe_dimension(),
+            bsk.glwe_size(),
+            bsk.polynomial_size(),
+            bsk.decomposition_base_log(),
+            bsk.decomposition_level_count(),
+        );
+        convert_standard_lwe_bootstrap_key_to_fourier(&bsk, &mut fourier_bsk);
+        drop(bsk);
+
+        let auto_keys = gen_all_auto_keys(
+            params.auto_base_log(),
+            params.auto_level(),
+            params.fft_type_auto(),
+            &cbs_glwe_key,
+            params.glwe_modular_std_dev(),
+            encryption_generator,
+        );
+        let ss_key = generate_scheme_switching_key(
+            &cbs_glwe_key,
+            params.ss_base_log(),
+            params.ss_level(),
+            params.glwe_modular_std_dev(),
+            params.ciphertext_modulus(),
+            encryption_generator,
+        );
+
+        Self {
+            params,
+            ksk,
+            fourier_bsk,
+            auto_keys,
+            ss_key,
+            fft: Fft::new(polynomial_size),
+        }
+    }
+
+    pub fn params(&self) -> &ProcessorParam<u64> {
+        &self.params
+    }
+
+    /// Keyswitching key from the digits' LWE key to the small LWE key of the bootstrapping.
+    pub fn ksk(&self) -> &LweStoredReusedKeyswitchKey<Vec<u64>> {
+        &self.ksk
+    }
+
+    pub fn fft(&self) -> &Fft {
+        &self.fft
+    }
+
+    /// LWE size of the table lookup results.
+    pub fn output_lwe_size(&self) -> LweSize {
+        LweSize(self.params.glwe_dimension().0 * self.params.polynomial_size().0 + 1)
+    }
+
+    fn allocate_ggsw_list(&self) -> FourierGgswCiphertextList<Vec<c64>> {
+        let glwe_size = self.params.glwe_dimension().to_glwe_size();
+        let polynomial_size = self.params.polynomial_size();
+        let count = self.params.extract_size();
+        FourierGgswCiphertextList::new(
+            vec![
+                c64::default();
+                count
+                    * polynomial_size.to_fourier_polynomial_size().0
+                    * glwe_size.0
+                    * glwe_size.0
+                    * self.params.cbs_level().0
+            ],
+            count,
+            glwe_size,
+            polynomial_size,
+            self.params.cbs_base_log(),
+            self.params.cbs_level(),
+        )
+    }
+}
+
+/// Lookup table in the vertical packing format, split into output chunks of `message_size` bits.
+pub struct CipherLut {
+    luts: Vec<PolynomialList<Vec<u64>>>,
+    pack_size: usize,
+    input_bits: usize,
+    chunk_count: usize,
+}
+
+impl CipherLut {
+    /// Build the lookup table mapping every `input_bits`-bit input `x` to the `output_bits`-bit
+    /// value `table[x]`.
+    ///
+    /// The result of a lookup is one LWE per chunk of `params.message_size()` bits, the most
+    /// significant chunk first. Every chunk keeps one bit of padding, like the digits of a
+    /// [`DigitDecomposer`], so that it can be bootstrapped again by
+    /// [`digit_recompose_lwes`](super::digit_recompose::digit_recompose_lwes).
+    pub fn from_table(
+        table: &[usize],
+        input_bits: usize,
+        output_bits: usize,
+        params: &ProcessorParam<u64>,
+    ) -> Self {
+        let chunk_size = params.message_size();
+        assert_eq!(
+            table.len(),
+            1 << input_bits,
+            "table must have 2^input_bits entries"
+        );
+        assert!(
+            output_bits > 0 && output_bits % chunk_size == 0,
+            "output_bits must be a positive multiple of the message size {chunk_size}"
+        );
+        let split_table = split_adjusted_lut_by_chunk(table, output_bits, chunk_size);
+        let (luts, pack_size) = generate_lut_from_vecs_auto(
+            &split_table,
+            params.polynomial_size(),
+            1 << (u64::BITS as usize - params.message_size() - 1),
+        );
+        Self {
+            luts,
+            pack_size,
+            input_bits,
+            chunk_count: output_bits / chunk_size,
+        }
+    }
+
+    /// Build the lookup table of `f` on `input_bits`-bit inputs.
+    ///
+    /// Only the `output_bits` least significant bits of every `f(x)` are kept.
+    pub fn from_fn<F>(
+        f: F,
+        input_bits: usize,
+        output_bits: usize,
+        params: &ProcessorParam<u64>,
+    ) -> Self
+    where
+        F: Fn(u64) -> u64,
+    {
+        let table: Vec<usize> = (0..1u64 << input_bits).map(|x| f(x) as usize).collect();
+        Self::from_table(&table, input_bits, output_bits, params)
+    }
+
+    pub fn input_bits(&self) -> usize {
+        self.input_bits
+    }
+
+    /// Number of output chunks, i.e. of LWE ciphertexts produced by a lookup.
+    pub fn chunk_count(&self) -> usize {
+        self.chunk_count
+    }
+}
+
+/// Circuit-bootstrap every digit of `digits` into `params.extract_size()` GGSW bits.
+///
+/// Every digit must be encoded on `extract_size` bits with one bit of padding, as produced by a
+/// [`DigitDecomposer`] in [`DigitMode::Unsigned`]. The bits of all digits are concatenated with
+/// the most significant digit first, whatever the `order` of `digits`.
+///
+/// Fails with [`ProcessorError::MissingAutomorphismKey`] if `keys` lacks a key of the trace,
+/// and with [`ProcessorError::EmptyGgswList`] if there is no digit.
+pub fn digits_to_ggsw_bits<DigitCont>(
+    digits: &GlweCiphertextList<DigitCont>,
+    order: DigitOrder,
+    keys: &CbsKeys,
+) -> Result<FourierGgswCiphertextList<Vec<c64>>, ProcessorError>
+where
+    DigitCont: Container<Element = u64> + Sync,
+{
+    let extract_lwe_size =
+        LweSize(digits.glwe_size().to_glwe_dimension().0 * digits.polynomial_size().0 + 1);
+    let mut ggsw_lists: Vec<_> = digits
+        .par_iter()
+        .map(|digit| {
+            let mut extract_input =
+                LweCiphertext::new(0u64, extract_lwe_size, digits.ciphertext_modulus());
+            extract_lwe_sample_from_glwe_ciphertext(&digit, &mut extract_input, MonomialDegree(0));
+            // 去掉 padding 位，使数字位于最高位
+            lwe_ciphertext_cleartext_mul_assign(&mut extract_input, Cleartext(2u64));
+            let mut ggsw_list = keys.allocate_ggsw_list();
+            circuit_bootstrapping_4_bits_at_once_rev_tr(
+                &extract_input,
+                &mut ggsw_list,
+                keys.fourier_bsk.as_view(),
+                &keys.auto_keys,
+                keys.ss_key.as_view(),
+                &keys.ksk,
+                &keys.params,
+            )?;
+            Ok(ggsw_list)
+        })
+        .collect::<Result<_, ProcessorError>>()?;
+    if order == DigitOrder::LsbFirst {
+        ggsw_lists.reverse();
+    }
+    concat_ggsw_lists(ggsw_lists, true)
+}
+
+/// Evaluate `lut` on the GGSW bits `ggsw_bits`, writing one LWE per output chunk of the table
+/// into `outputs`.
+pub fn vertical_packing_lookup(
+    ggsw_bits: &FourierGgswCiphertextList<Vec<c64>>,
+    lut: &CipherLut,
+    outputs: &mut [LweCiphertext<Vec<u64>>],
+    fft: &Fft,
+) {
+    assert_eq!(
+        ggsw_bits.count(),
+        lut.input_bits,
+        "the number of GGSW bits must match the table input size"
+    );
+    assert_eq!(
+        outputs.len(),
+        lut.chunk_count,
+        "one output is needed per chunk"
+    );
+    let ggsw_view = ggsw_bits.as_view();
+    let fft_view = fft.as_view();
+
+    let group_size = lut.pack_size.min(lut.chunk_count);
+    let lut_size = 1_usize << lut.input_bits;
+
+    lut.luts
+        .par_iter()
+        .zip(outputs.par_chunks_mut(group_size))
+        .for_each(|(lut, lwe_group)| {
+            let mut local_buffer = ComputationBuffers::new();
+            let need = vertical_packing_scratch::<u64>(
+                ggsw_view.glwe_size(),
+                ggsw_view.polynomial_size(),
+                lut.polynomial_count(),
+                ggsw_view.count(),
+                fft_view,
+            )
+            .unwrap()
+            .unaligned_bytes_required();
+            local_buffer.resize(need);
+
+            let stack = local_buffer.stack();
+            let temp = horizontal_vertical_packing_without_extract(
+                lut.as_view(),
+                ggsw_view,
+                fft_view,
+                stack,
+                lwe_group[0].ciphertext_modulus(),
+            );
+            for (i, lwe) in lwe_group.iter_mut().enumerate() {
+                extract_lwe_sample_from_glwe_ciphertext(&temp, lwe, MonomialDegree(i * lut_size));
+            }
+        });
+}
+
+/// Evaluate `lut` on the large-precision plaintext encrypted in `input`.
+///
+/// `input` is decomposed by `decomposer`, whose digits must all be `extract_size` bits wide,
+/// then circuit-bootstrapped and used as selectors of the vertical packing. Returns one LWE per
+/// output chunk of `lut`, the most significant chunk first.
+///
+/// Fails when the circuit bootstrapping does, see [`digits_to_ggsw_bits`].
+pub fn decompose_and_lookup<BskCont, KskCont, InputCont>(
+    decomposer: &DigitDecomposer<BskCont, KskCont>,
+    input: &GlweCiphertext<InputCont>,
+    lut: &CipherLut,
+    keys: &CbsKeys,
+) -> Result<Vec<LweCiphertext<Vec<u64>>>, ProcessorError>
+where
+    BskCont: Container<Element = c64>,
+    KskCont: Container<Element = c64>,
+    InputCont: Container<Element = u64>,
+{
+    assert_eq!(
+        decomposer.mode(),
+        DigitMode::Unsigned,
+        "circuit bootstrapping needs unsigned digits"
+    );
+    assert!(
+        decomposer
+            .digit_widths()
+            .iter()
+            .all(|&width| width == keys.params.extract_size()),
+        "every digit must be {} bits wide",
+        keys.params.extract_size()
+    );
+    assert_eq!(
+        decomposer.digit_widths().iter().sum::<usize>(),
+        lut.input_bits,
+        "the decomposition must cover the table input size"
+    );
+
+    let digits = decomposer.decompose(input);
+    let ggsw_bits = digits_to_ggsw_bits(&digits, decomposer.order(), keys)?;
+
+    let output = LweCiphertext::new(0u64, keys.output_lwe_size(), input.ciphertext_modulus());
+    let mut outputs = vec![output; lut.chunk_count];
+    vertical_packing_lookup(&ggsw_bits, lut, &mut outputs, &keys.fft);
+    Ok(outputs)
+}
+
+/// Evaluate `f` on the `n_bits = base_log * level` bit plaintext encrypted in `input`.
+///
+/// Returns the `output_bits` least significant bits of `f(x)` as one LWE per chunk of
+/// `message_size` bits, the most significant chunk first. Build the table once with
+/// [`CipherLut::from_fn`] and call [`decompose_and_lookup`] when `f` is evaluated on many inputs.
+pub fn evaluate_function<BskCont, KskCont, InputCont, F>(
+    decomposer: &DigitDecomposer<BskCont, KskCont>,
+    input: &GlweCiphertext<InputCont>,
+    f: F,
+    output_bits: usize,
+    keys: &CbsKeys,
+) -> Result<Vec<LweCiphertext<Vec<u64>>>, ProcessorError>
+where
+    BskCont: Container<Element = c64>,
+    KskCont: Container<Element = c64>,
+    InputCont: Container<Element = u64>,
+    F: Fn(u64) -> u64,
+{
+    let n_bits = decomposer.digit_widths().iter().sum();
+    let lut = CipherLut::from_fn(f, n_bits, output_bits, keys.params());
+    decompose_and_lookup(decomposer, input, &lut, keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use fhe_processor::utils::instance::SetI;
+    use rand::Rng;
+    use tfhe::core_crypto::prelude::{DecompositionBaseLog, DecompositionLevelCount};
+
+    use super::*;
+    use crate::algorithms::test_utils::new_test_context;
+
+    #[test]
+    fn test_decompose_and_lookup_identity() {
+        let mut rng = rand::thread_rng();
+        let (base_log, level) = (4, 2);
+        let n_bits = base_log * level;
+        let mut ctx = new_test_context(
+            DecompositionBaseLog(base_log),
+            DecompositionLevelCount(level),
+        );
+        let mut keys = CbsKeys::new(&ctx.glwe_key, *SetI, &mut ctx.encryption_generator);
+        let table: Vec<usize> = (0..1 << n_bits).collect();
+        let lut = CipherLut::from_table(&table, n_bits, n_bits, keys.params());
+        let message_size = keys.params().message_size();
+
+        for order in [DigitOrder::LsbFirst, DigitOrder::MsbFirst] {
+            ctx.decomposer.set_order(order);
+            let value = rng.r#gen::<u64>() % (1 << n_bits);
+            let input = ctx.encrypt(value, n_bits);
+            let outputs = decompose_and_lookup(&ctx.decomposer, &input, &lut, &keys).unwrap();
+            let result = ctx.decrypt_chunks(&outputs, message_size);
+            assert_eq!(result, value, "order {order:?}");
+        }
+
+        // the trace of the circuit bootstrapping needs every automorphism key
+        keys.auto_keys.clear();
+        let input = ctx.encrypt(0, n_bits);
+        assert!(matches!(
+            decompose_and_lookup(&ctx.decomposer, &input, &lut, &keys),
+            Err(ProcessorError::MissingAutomorphismKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_evaluate_function() {
+        let mut rng = rand::thread_rng();
+        let base_log = 4;
+        let mut ctx = new_test_context(DecompositionBaseLog(base_log), DecompositionLevelCount(2));
+        ctx.decomposer.set_order(DigitOrder::MsbFirst);
+        let keys = CbsKeys::new(&ctx.glwe_key, *SetI, &mut ctx.encryption_generator);
+        let message_size = keys.params().message_size();
+
+        let functions: [(&str, fn(u64) -> u64); 3] = [
+            ("identity", |x| x),
+            ("square", |x| x * x),
+            ("isqrt", |x| x.isqrt()),
+        ];
+        for level in 2..=5 {
+            let n_bits = base_log * level;
+            ctx.decomposer
+                .set_level_count(DecompositionLevelCount(level));
+            for (name, f) in functions {
+                let value = rng.r#gen::<u64>() % (1 << n_bits);
+                let input = ctx.encrypt(value, n_bits);
+                let outputs = evaluate_function(&ctx.decomposer, &input, f, n_bits, &keys).unwrap();
+                assert_eq!(outputs.len(), level);
+                let result = ctx.decrypt_chunks(&outputs, message_size);
+                assert_eq!(
+                    result,
+                    f(value) % (1 << n_bits),
+                    "{name} of {value} at level {level}"
+                );
+            }
+        }
+    }
+}
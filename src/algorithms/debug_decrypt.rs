@@ -0,0 +1,142 @@
+//! Key-dependent variants of the decomposition, only built with the `debug-decrypt` feature.
+//!
+//! They run exactly the same homomorphic pipeline as the production entry points, but decrypt
+//! every intermediate ciphertext with the provided secret key and print its constant
+//! coefficient. Never enable this feature in a server build.
+use std::fmt::Binary;
+
+use concrete_fft::c64;
+use refined_tfhe_lhe::FourierGlweKeyswitchKey;
+use tfhe::core_crypto::prelude::{
+    CastFrom, CastInto, Container, ContainerMut, DecompositionBaseLog, DecompositionLevelCount,
+    FourierLweBootstrapKey, GlweCiphertext, GlweCiphertextList, GlweCiphertextView, GlweSecretKey,
+    LweCiphertextView, PlaintextCount, PlaintextList, SignedDecomposer, UnsignedInteger,
+    UnsignedTorus, decrypt_glwe_ciphertext, decrypt_lwe_ciphertext,
+};
+
+use crate::algorithms::digit_decompose::{
+    DecomposeBuffers, DecomposeTrace, DigitMode, DigitOrder, LevelLuts,
+    digit_decompose_no_padding_traced, digit_decompose_with_padding_traced,
+};
+
+struct DecryptTrace<'a, Scalar: UnsignedInteger> {
+    glwe_key: &'a GlweSecretKey<Vec<Scalar>>,
+    decomposer: SignedDecomposer<Scalar>,
+}
+
+impl<'a, Scalar: UnsignedTorus> DecryptTrace<'a, Scalar> {
+    fn new(glwe_key: &'a GlweSecretKey<Vec<Scalar>>, base_log: DecompositionBaseLog) -> Self {
+        // keep the padding bit and one extra bit of the next digit visible
+        let decomposer = SignedDecomposer::new(
+            DecompositionBaseLog(base_log.0 + 2),
+            DecompositionLevelCount(1),
+        );
+        Self {
+            glwe_key,
+            decomposer,
+        }
+    }
+}
+
+impl<Scalar: UnsignedTorus + Binary> DecomposeTrace<Scalar> for DecryptTrace<'_, Scalar> {
+    fn lwe(&self, label: &str, lwe: LweCiphertextView<'_, Scalar>) {
+        let plain = decrypt_lwe_ciphertext(&self.glwe_key.as_lwe_secret_key(), &lwe);
+        let decode = self.decomposer.closest_representable(plain.0);
+        println!("{}:\t{:0width$b}", label, decode, width = Scalar::BITS);
+    }
+
+    fn glwe(&self, label: &str, glwe: GlweCiphertextView<'_, Scalar>) {
+        let mut plain_list =
+            PlaintextList::new(Scalar::ZERO, PlaintextCount(glwe.polynomial_size().0));
+        decrypt_glwe_ciphertext(self.glwe_key, &glwe, &mut plain_list);
+        let decode = self.decomposer.closest_representable(*plain_list.get(0).0);
+        println!("{}:\t{:0width$b}", label, decode, width = Scalar::BITS);
+    }
+}
+
+/// Debug variant of [`digit_decompose_no_padding`](super::digit_decompose::digit_decompose_no_padding).
+pub fn digit_decompose_no_padding_debug<Scalar, InputCont, OutputCont, BskCont, KskCont>(
+    input: &GlweCiphertext<InputCont>,
+    output: &mut GlweCiphertextList<OutputCont>,
+    decompose_base_log: DecompositionBaseLog,
+    decompose_level: DecompositionLevelCount,
+    fourier_bsk: &FourierLweBootstrapKey<BskCont>,
+    ksk: &FourierGlweKeyswitchKey<KskCont>,
+    glwe_key: &GlweSecretKey<Vec<Scalar>>,
+) where
+    Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger + Binary,
+    InputCont: Container<Element = Scalar>,
+    OutputCont: ContainerMut<Element = Scalar>,
+    BskCont: Container<Element = c64>,
+    KskCont: Container<Element = c64>,
+{
+    digit_decompose_no_padding_traced(
+        input,
+        output,
+        decompose_base_log,
+        decompose_level,
+        fourier_bsk,
+        ksk,
+        &DecryptTrace::new(glwe_key, decompose_base_log),
+    );
+}
+
+/// Debug variant of [`digit_decompose_with_padding`](super::digit_decompose::digit_decompose_with_padding).
+pub fn digit_decompose_with_padding_debug<Scalar, InputCont, OutputCont, BskCont, KskCont>(
+    input: &GlweCiphertext<InputCont>,
+    output: &mut GlweCiphertextList<OutputCont>,
+    decompose_base_log: DecompositionBaseLog,
+    decompose_level: DecompositionLevelCount,
+    fourier_bsk: &FourierLweBootstrapKey<BskCont>,
+    ksk: &FourierGlweKeyswitchKey<KskCont>,
+    glwe_key: &GlweSecretKey<Vec<Scalar>>,
+) where
+    Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger + Binary,
+    InputCont: Container<Element = Scalar>,
+    OutputCont: ContainerMut<Element = Scalar>,
+    BskCont: Container<Element = c64>,
+    KskCont: Container<Element = c64>,
+{
+    digit_decompose_with_widths_debug(
+        input,
+        output,
+        &vec![decompose_base_log.0; decompose_level.0],
+        fourier_bsk,
+        ksk,
+        DigitMode::Unsigned,
+        DigitOrder::LsbFirst,
+        glwe_key,
+    );
+}
+
+/// Debug variant of [`digit_decompose_with_widths`](super::digit_decompose::digit_decompose_with_widths).
+pub fn digit_decompose_with_widths_debug<Scalar, InputCont, OutputCont, BskCont, KskCont>(
+    input: &GlweCiphertext<InputCont>,
+    output: &mut GlweCiphertextList<OutputCont>,
+    digit_widths: &[usize],
+    fourier_bsk: &FourierLweBootstrapKey<BskCont>,
+    ksk: &FourierGlweKeyswitchKey<KskCont>,
+    mode: DigitMode,
+    order: DigitOrder,
+    glwe_key: &GlweSecretKey<Vec<Scalar>>,
+) where
+    Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger + Binary,
+    InputCont: Container<Element = Scalar>,
+    OutputCont: ContainerMut<Element = Scalar>,
+    BskCont: Container<Element = c64>,
+    KskCont: Container<Element = c64>,
+{
+    let max_width = digit_widths.iter().copied().max().unwrap_or(1);
+    let luts = LevelLuts::new(digit_widths, fourier_bsk.polynomial_size());
+    digit_decompose_with_padding_traced(
+        input,
+        output,
+        &luts,
+        fourier_bsk,
+        ksk,
+        mode,
+        order,
+        &mut DecomposeBuffers::new(&luts, fourier_bsk, ksk, input.ciphertext_modulus()),
+        &DecryptTrace::new(glwe_key, DecompositionBaseLog(max_width)),
+    );
+}
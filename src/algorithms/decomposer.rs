@@ -0,0 +1,686 @@
+use concrete_fft::c64;
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use refined_tfhe_lhe::{FourierGlweKeyswitchKey, glwe_ciphertext_monic_monomial_div};
+#[cfg(feature = "debug-decrypt")]
+use tfhe::core_crypto::prelude::GlweSecretKey;
+use tfhe::core_crypto::prelude::{
+    CastFrom, CastInto, CiphertextModulus, Container, ContainerMut, ContiguousEntityContainer,
+    DecompositionBaseLog, DecompositionLevelCount, FourierLweBootstrapKey, GlweCiphertext,
+    GlweCiphertextCount, GlweCiphertextList, MonomialDegree, UnsignedInteger, UnsignedTorus,
+};
+
+#[cfg(feature = "debug-decrypt")]
+use crate::algorithms::debug_decrypt::digit_decompose_with_widths_debug;
+
+use crate::algorithms::digit_decompose::{
+    DecomposeBuffers, DigitMode, DigitOrder, LevelLuts, NoTrace, digit_decompose_no_padding,
+    digit_decompose_with_padding_traced,
+};
+
+/// Homomorphic digit decomposer of a large-precision GLWE ciphertext.
+///
+/// Owns the evaluation keys used by the decomposition (the Fourier bootstrapping key and the
+/// Fourier GLWE keyswitching key) together with the decomposition parameters, so that the same
+/// object can be reused for every input.
+pub struct DigitDecomposer<BskCont, KskCont>
+where
+    BskCont: Container<Element = c64>,
+    KskCont: Container<Element = c64>,
+{
+    fourier_bsk: FourierLweBootstrapKey<BskCont>,
+    ksk: FourierGlweKeyswitchKey<KskCont>,
+    base_log: DecompositionBaseLog,
+    /// 每一层的位数（低位在前）及其 LUT
+    luts: LevelLuts,
+    mode: DigitMode,
+    order: DigitOrder,
+}
+
+impl<BskCont, KskCont> DigitDecomposer<BskCont, KskCont>
+where
+    BskCont: Container<Element = c64>,
+    KskCont: Container<Element = c64>,
+{
+    pub fn new(
+        fourier_bsk: FourierLweBootstrapKey<BskCont>,
+        ksk: FourierGlweKeyswitchKey<KskCont>,
+        base_log: DecompositionBaseLog,
+        level: DecompositionLevelCount,
+    ) -> Self {
+        assert!(level.0 >= 1, "level must be at least 1");
+        let luts = LevelLuts::new(&vec![base_log.0; level.0], fourier_bsk.polynomial_size());
+        Self {
+            fourier_bsk,
+            ksk,
+            base_log,
+            luts,
+            mode: DigitMode::Unsigned,
+            order: DigitOrder::LsbFirst,
+        }
+    }
+
+    pub fn fourier_bsk(&self) -> &FourierLweBootstrapKey<BskCont> {
+        &self.fourier_bsk
+    }
+
+    pub fn ksk(&self) -> &FourierGlweKeyswitchKey<KskCont> {
+        &self.ksk
+    }
+
+    /// Width of every level, unless set otherwise by [`DigitDecomposer::set_digit_widths`].
+    pub fn base_log(&self) -> DecompositionBaseLog {
+        self.base_log
+    }
+
+    pub fn level_count(&self) -> DecompositionLevelCount {
+        DecompositionLevelCount(self.luts.widths().len())
+    }
+
+    /// Use `level` digits of `base_log` bits each.
+    pub fn set_level_count(&mut self, level: DecompositionLevelCount) {
+        assert!(level.0 >= 1, "level must be at least 1");
+        self.luts = LevelLuts::new(
+            &vec![self.base_log.0; level.0],
+            self.fourier_bsk.polynomial_size(),
+        );
+    }
+
+    /// Width of each digit, least significant first.
+    pub fn digit_widths(&self) -> &[usize] {
+        self.luts.widths()
+    }
+
+    /// Use one width per level, least significant first, e.g. `[4, 4, 3, 1]` to split a 12-bit
+    /// input so that the last digit fits a smaller lookup table.
+    ///
+    /// Every width must be between 1 bit and the log2 of the polynomial size. The widths must
+    /// also add up to less than the bits of the ciphertext scalar, which is checked when
+    /// decomposing.
+    pub fn set_digit_widths(&mut self, digit_widths: &[usize]) {
+        self.luts = LevelLuts::new(digit_widths, self.fourier_bsk.polynomial_size());
+    }
+
+    pub fn mode(&self) -> DigitMode {
+        self.mode
+    }
+
+    /// Select the digit range of [`DigitDecomposer::decompose`], [`DigitMode::Unsigned`] by
+    /// default.
+    pub fn set_mode(&mut self, mode: DigitMode) {
+        self.mode = mode;
+    }
+
+    pub fn order(&self) -> DigitOrder {
+        self.order
+    }
+
+    /// Select the order of the output list, [`DigitOrder::LsbFirst`] by default.
+    /// [`DigitDecomposer::digit_widths`] is always given least significant first.
+    pub fn set_order(&mut self, order: DigitOrder) {
+        self.order = order;
+    }
+
+    /// Decompose `input` into one digit per entry of [`DigitDecomposer::digit_widths`].
+    ///
+    /// Every digit is encoded with one bit of padding on its own width, in the order selected by
+    /// [`DigitDecomposer::set_order`]. In [`DigitMode::Balanced`] the padding bit holds the sign
+    /// of the digit.
+    ///
+    /// Allocates the scratch ciphertexts and the f1 accumulators on every call, use
+    /// [`DigitDecomposer::decompose_with`] in loops.
+    pub fn decompose<Scalar, InputCont>(
+        &self,
+        input: &GlweCiphertext<InputCont>,
+    ) -> GlweCiphertextList<Vec<Scalar>>
+    where
+        Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger,
+        InputCont: Container<Element = Scalar>,
+    {
+        let mut output = self.allocate_output(input);
+        self.decompose_into(input, &mut output);
+        output
+    }
+
+    pub fn decompose_into<Scalar, InputCont, OutputCont>(
+        &self,
+        input: &GlweCiphertext<InputCont>,
+        output: &mut GlweCiphertextList<OutputCont>,
+    ) where
+        Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger,
+        InputCont: Container<Element = Scalar>,
+        OutputCont: ContainerMut<Element = Scalar>,
+    {
+        let mut buffers = self.allocate_buffers(input.ciphertext_modulus());
+        self.decompose_with(input, output, &mut buffers);
+    }
+
+    /// Same as [`DigitDecomposer::decompose_into`], with scratch ciphertexts and f1
+    /// accumulators from [`DigitDecomposer::allocate_buffers`], so that the call does no heap
+    /// allocation.
+    ///
+    /// The buffers must be allocated after the last change of the digit widths.
+    pub fn decompose_with<Scalar, InputCont, OutputCont>(
+        &self,
+        input: &GlweCiphertext<InputCont>,
+        output: &mut GlweCiphertextList<OutputCont>,
+        buffers: &mut DecomposeBuffers<Scalar>,
+    ) where
+        Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger,
+        InputCont: Container<Element = Scalar>,
+        OutputCont: ContainerMut<Element = Scalar>,
+    {
+        digit_decompose_with_padding_traced(
+            input,
+            output,
+            &self.luts,
+            &self.fourier_bsk,
+            &self.ksk,
+            self.mode,
+            self.order,
+            buffers,
+            &NoTrace,
+        );
+    }
+
+    /// Decompose every ciphertext of `inputs` in parallel, returning one digit list per input.
+    ///
+    /// The scratch ciphertexts and per-level LUTs are allocated once per rayon job and reused
+    /// for all the inputs it processes.
+    pub fn decompose_batch<Scalar, InputCont>(
+        &self,
+        inputs: &[GlweCiphertext<InputCont>],
+    ) -> Vec<GlweCiphertextList<Vec<Scalar>>>
+    where
+        Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger + Send + Sync,
+        InputCont: Container<Element = Scalar> + Sync,
+        BskCont: Sync,
+        KskCont: Sync,
+    {
+        let Some(first) = inputs.first() else {
+            return Vec::new();
+        };
+        let ciphertext_modulus = first.ciphertext_modulus();
+        inputs
+            .par_iter()
+            .map_init(
+                || self.allocate_buffers(ciphertext_modulus),
+                |buffers, input| self.decompose_with_buffers(input, buffers),
+            )
+            .collect()
+    }
+
+    /// Same as [`DigitDecomposer::decompose_batch`] for the ciphertexts of a list.
+    pub fn decompose_list<Scalar, InputCont>(
+        &self,
+        inputs: &GlweCiphertextList<InputCont>,
+    ) -> Vec<GlweCiphertextList<Vec<Scalar>>>
+    where
+        Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger + Send + Sync,
+        InputCont: Container<Element = Scalar> + Sync,
+        BskCont: Sync,
+        KskCont: Sync,
+    {
+        let ciphertext_modulus = inputs.ciphertext_modulus();
+        inputs
+            .par_iter()
+            .map_init(
+                || self.allocate_buffers(ciphertext_modulus),
+                |buffers, input| self.decompose_with_buffers(&input, buffers),
+            )
+            .collect()
+    }
+
+    /// Decompose the first `slot_count` coefficients of a packed `input`, returning one digit
+    /// list per slot, in slot order.
+    ///
+    /// Slot `i` is rotated to the constant coefficient with `X^(-i)` and decomposed like
+    /// [`DigitDecomposer::decompose`], so the digits of every slot sit in the constant
+    /// coefficient of their own list. Slots are processed in parallel.
+    pub fn decompose_slots<Scalar, InputCont>(
+        &self,
+        input: &GlweCiphertext<InputCont>,
+        slot_count: usize,
+    ) -> Vec<GlweCiphertextList<Vec<Scalar>>>
+    where
+        Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger + Send + Sync,
+        InputCont: Container<Element = Scalar> + Sync,
+        BskCont: Sync,
+        KskCont: Sync,
+    {
+        assert!(
+            slot_count <= input.polynomial_size().0,
+            "slot_count must not exceed the polynomial size"
+        );
+        let ciphertext_modulus = input.ciphertext_modulus();
+        (0..slot_count)
+            .into_par_iter()
+            .map_init(
+                || {
+                    let rotated = GlweCiphertext::new(
+                        Scalar::ZERO,
+                        input.glwe_size(),
+                        input.polynomial_size(),
+                        ciphertext_modulus,
+                    );
+                    (self.allocate_buffers(ciphertext_modulus), rotated)
+                },
+                |(buffers, rotated), slot| {
+                    glwe_ciphertext_monic_monomial_div(rotated, input, MonomialDegree(slot));
+                    self.decompose_with_buffers(rotated, buffers)
+                },
+            )
+            .collect()
+    }
+
+    /// Same as [`DigitDecomposer::decompose`], printing every intermediate value decrypted
+    /// under `glwe_key`.
+    #[cfg(feature = "debug-decrypt")]
+    pub fn decompose_debug<Scalar, InputCont>(
+        &self,
+        input: &GlweCiphertext<InputCont>,
+        glwe_key: &GlweSecretKey<Vec<Scalar>>,
+    ) -> GlweCiphertextList<Vec<Scalar>>
+    where
+        Scalar:
+            UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger + std::fmt::Binary,
+        InputCont: Container<Element = Scalar>,
+    {
+        let mut output = self.allocate_output(input);
+        digit_decompose_with_widths_debug(
+            input,
+            &mut output,
+            self.digit_widths(),
+            &self.fourier_bsk,
+            &self.ksk,
+            self.mode,
+            self.order,
+            glwe_key,
+        );
+        output
+    }
+
+    /// Same as [`DigitDecomposer::decompose`], but the digits are produced without padding.
+    /// Always unsigned and least significant first, and only for uniform widths.
+    pub fn decompose_no_padding<Scalar, InputCont>(
+        &self,
+        input: &GlweCiphertext<InputCont>,
+    ) -> GlweCiphertextList<Vec<Scalar>>
+    where
+        Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger,
+        InputCont: Container<Element = Scalar>,
+    {
+        assert!(
+            self.digit_widths().iter().all(|w| *w == self.base_log.0),
+            "the decomposition without padding only supports uniform widths"
+        );
+        let mut output = self.allocate_output(input);
+        digit_decompose_no_padding(
+            input,
+            &mut output,
+            self.base_log,
+            self.level_count(),
+            &self.fourier_bsk,
+            &self.ksk,
+        );
+        output
+    }
+
+    /// Scratch ciphertexts and f1 accumulators of [`DigitDecomposer::decompose_with`] for the
+    /// current digit widths.
+    pub fn allocate_buffers<Scalar>(
+        &self,
+        ciphertext_modulus: CiphertextModulus<Scalar>,
+    ) -> DecomposeBuffers<Scalar>
+    where
+        Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize>,
+    {
+        DecomposeBuffers::new(&self.luts, &self.fourier_bsk, &self.ksk, ciphertext_modulus)
+    }
+
+    fn decompose_with_buffers<Scalar, InputCont>(
+        &self,
+        input: &GlweCiphertext<InputCont>,
+        buffers: &mut DecomposeBuffers<Scalar>,
+    ) -> GlweCiphertextList<Vec<Scalar>>
+    where
+        Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger,
+        InputCont: Container<Element = Scalar>,
+    {
+        let mut output = self.allocate_output(input);
+        self.decompose_with(input, &mut output, buffers);
+        output
+    }
+
+    /// Digit list of [`DigitDecomposer::decompose_into`] for ciphertexts shaped like `input`.
+    pub fn allocate_output<Scalar, InputCont>(
+        &self,
+        input: &GlweCiphertext<InputCont>,
+    ) -> GlweCiphertextList<Vec<Scalar>>
+    where
+        Scalar: UnsignedTorus,
+        InputCont: Container<Element = Scalar>,
+    {
+        GlweCiphertextList::new(
+            Scalar::ZERO,
+            input.glwe_size(),
+            input.polynomial_size(),
+            GlweCiphertextCount(self.digit_widths().len()),
+            input.ciphertext_modulus(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jemalloc_ctl::thread;
+    use rand::Rng;
+    use refined_tfhe_lhe::{FftType, keyswitch_lwe_ciphertext_by_glwe_keyswitch};
+    use tfhe::core_crypto::prelude::{
+        ContiguousEntityContainer, GlweSize, LweCiphertext, LweDimension, PolynomialSize,
+        SignedDecomposer,
+    };
+
+    use super::*;
+    use crate::algorithms::digit_decompose::balanced_digits;
+    use crate::algorithms::test_utils::new_test_context;
+
+    /// `SignedDecomposer::decompose` of the `base_log * level` bit `value`, least significant
+    /// digit first.
+    ///
+    /// `SignedDecomposer` may keep a `+B/2` digit where [`DigitMode::Balanced`] carries it into
+    /// the next level as `-B/2`, so those digits are carried here too.
+    fn signed_decomposer_digits(value: u64, base_log: usize, level: usize) -> Vec<i64> {
+        let decomposer = SignedDecomposer::<u64>::new(
+            DecompositionBaseLog(base_log),
+            DecompositionLevelCount(level),
+        );
+        let mut digits = vec![0_i64; level];
+        // level 1 of SignedDecomposer is the most significant digit
+        for term in decomposer.decompose(value << (64 - base_log * level)) {
+            digits[level - term.level().0] = term.value() as i64;
+        }
+        let mut carry = 0;
+        for digit in digits.iter_mut() {
+            *digit += carry;
+            carry = 0;
+            if *digit >= 1 << (base_log - 1) {
+                *digit -= 1 << base_log;
+                carry = 1;
+            }
+        }
+        digits
+    }
+
+    #[test]
+    fn test_balanced_decompose() {
+        let mut rng = rand::thread_rng();
+        for (base_log, level) in [(2, 4), (4, 3), (5, 2)] {
+            let n_bits = base_log * level;
+            let half_digits: u64 = (0..level).map(|j| 1 << (j * base_log + base_log - 1)).sum();
+            // around +-2^(n-1) and with every digit at B/2, where the carry runs through all
+            // the levels
+            let mut values = vec![
+                0,
+                1,
+                (1 << (n_bits - 1)) - 1,
+                1 << (n_bits - 1),
+                (1 << (n_bits - 1)) + 1,
+                (1 << n_bits) - 1,
+                half_digits,
+                half_digits - 1,
+            ];
+            values.extend((0..8).map(|_| rng.r#gen::<u64>() % (1 << n_bits)));
+
+            let mut ctx = new_test_context(
+                DecompositionBaseLog(base_log),
+                DecompositionLevelCount(level),
+            );
+            ctx.decomposer.set_mode(DigitMode::Balanced);
+            for value in values {
+                let input = ctx.encrypt(value, n_bits);
+                let output = ctx.decomposer.decompose(&input);
+                let digits: Vec<i64> = output
+                    .iter()
+                    .map(|digit| ctx.decrypt_signed(&digit, base_log + 1))
+                    .collect();
+                assert_eq!(
+                    digits,
+                    signed_decomposer_digits(value, base_log, level),
+                    "value {value}"
+                );
+                assert_eq!(
+                    digits,
+                    balanced_digits(value, &vec![base_log; level]),
+                    "value {value}"
+                );
+            }
+        }
+    }
+
+    /// Decomposer with all-zero keys of the `main.rs` shapes, for the checks that fire before
+    /// any key is used.
+    fn zero_key_decomposer(base_log: usize, level: usize) -> DigitDecomposer<Vec<c64>, Vec<c64>> {
+        let fourier_bsk = FourierLweBootstrapKey::new(
+            LweDimension(1),
+            GlweSize(2),
+            PolynomialSize(2048),
+            DecompositionBaseLog(26),
+            DecompositionLevelCount(1),
+        );
+        let ksk = FourierGlweKeyswitchKey::new(
+            GlweSize(3),
+            GlweSize(2),
+            PolynomialSize(1024),
+            DecompositionBaseLog(4),
+            DecompositionLevelCount(6),
+            FftType::Vanilla,
+        );
+        DigitDecomposer::new(
+            fourier_bsk,
+            ksk,
+            DecompositionBaseLog(base_log),
+            DecompositionLevelCount(level),
+        )
+    }
+
+    #[test]
+    #[should_panic(expected = "less than 32 bits")]
+    fn test_u32_rejects_wide_digits() {
+        // 32 bits of digits fit a u64 torus but leave no padding bit in a u32 one
+        let decomposer = zero_key_decomposer(8, 4);
+        let input = GlweCiphertext::new(
+            0_u32,
+            GlweSize(2),
+            PolynomialSize(2048),
+            CiphertextModulus::new_native(),
+        );
+        decomposer.decompose(&input);
+    }
+
+    #[test]
+    #[should_panic(expected = "same digit widths")]
+    fn test_decompose_with_rejects_stale_buffers() {
+        let mut decomposer = zero_key_decomposer(4, 3);
+        let mut buffers = decomposer.allocate_buffers(CiphertextModulus::<u64>::new_native());
+        // same level count, other f1 accumulators
+        decomposer.set_digit_widths(&[3, 5, 4]);
+        let input = GlweCiphertext::new(
+            0_u64,
+            GlweSize(2),
+            PolynomialSize(2048),
+            CiphertextModulus::new_native(),
+        );
+        let mut output = decomposer.allocate_output(&input);
+        decomposer.decompose_with(&input, &mut output, &mut buffers);
+    }
+
+    #[test]
+    fn test_mixed_width_decompose() {
+        let mut rng = rand::thread_rng();
+        let mut ctx = new_test_context(DecompositionBaseLog(4), DecompositionLevelCount(3));
+        for widths in [vec![4, 4, 3, 1], vec![3, 5], vec![5, 3], vec![2, 4, 2, 3]] {
+            let n_bits: usize = widths.iter().sum();
+            ctx.decomposer.set_digit_widths(&widths);
+            for mode in [DigitMode::Unsigned, DigitMode::Balanced] {
+                ctx.decomposer.set_mode(mode);
+                for _ in 0..4 {
+                    let value = rng.r#gen::<u64>() % (1 << n_bits);
+                    let input = ctx.encrypt(value, n_bits);
+                    let output = ctx.decomposer.decompose(&input);
+                    let digits: Vec<i64> = output
+                        .iter()
+                        .zip(widths.iter())
+                        .map(|(digit, width)| ctx.decrypt_signed(&digit, width + 1))
+                        .collect();
+                    let expected = match mode {
+                        DigitMode::Unsigned => {
+                            let mut rest = value;
+                            widths
+                                .iter()
+                                .map(|width| {
+                                    let digit = (rest % (1 << width)) as i64;
+                                    rest >>= width;
+                                    digit
+                                })
+                                .collect()
+                        }
+                        DigitMode::Balanced => balanced_digits(value, &widths),
+                    };
+                    assert_eq!(
+                        digits, expected,
+                        "widths {widths:?}, {mode:?}, value {value}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_msb_first_order() {
+        let mut rng = rand::thread_rng();
+        let widths = [4, 4, 3, 1];
+        let n_bits: usize = widths.iter().sum();
+        let mut ctx = new_test_context(DecompositionBaseLog(4), DecompositionLevelCount(3));
+        ctx.decomposer.set_digit_widths(&widths);
+        ctx.decomposer.set_order(DigitOrder::MsbFirst);
+        for _ in 0..4 {
+            let value = rng.r#gen::<u64>() % (1 << n_bits);
+            let input = ctx.encrypt(value, n_bits);
+            let output = ctx.decomposer.decompose(&input);
+            let digits: Vec<i64> = output
+                .iter()
+                .zip(widths.iter().rev())
+                .map(|(digit, width)| ctx.decrypt_signed(&digit, width + 1))
+                .collect();
+            let mut expected = Vec::new();
+            let mut rest = value;
+            for width in widths {
+                expected.push((rest % (1 << width)) as i64);
+                rest >>= width;
+            }
+            expected.reverse();
+            assert_eq!(digits, expected, "value {value}");
+        }
+    }
+
+    #[test]
+    fn test_decompose_with_does_not_allocate() {
+        let (base_log, level) = (4, 3);
+        let n_bits = base_log * level;
+        let value = 0xa63;
+        let mut ctx = new_test_context(
+            DecompositionBaseLog(base_log),
+            DecompositionLevelCount(level),
+        );
+        let input = ctx.encrypt(value, n_bits);
+        let decomposer = &ctx.decomposer;
+        let mut buffers = decomposer.allocate_buffers(input.ciphertext_modulus());
+        let mut output = decomposer.allocate_output(&input);
+        decomposer.decompose_with(&input, &mut output, &mut buffers);
+
+        // fhe_processor installs jemalloc as the global allocator
+        let allocated = thread::allocatedp::mib().unwrap().read().unwrap();
+
+        // the GLWE keyswitch of refined-tfhe-lhe allocates its own scratch, everything else
+        // has to come from the buffers
+        let ksk = decomposer.ksk();
+        let lwe_size = |glwe_size: GlweSize| {
+            LweDimension(glwe_size.to_glwe_dimension().0 * ksk.polynomial_size().0).to_lwe_size()
+        };
+        let extract_input = LweCiphertext::new(
+            0_u64,
+            lwe_size(ksk.input_glwe_size()),
+            input.ciphertext_modulus(),
+        );
+        let mut after_ks = LweCiphertext::new(
+            0_u64,
+            lwe_size(ksk.output_glwe_size()),
+            input.ciphertext_modulus(),
+        );
+        let before = allocated.get();
+        keyswitch_lwe_ciphertext_by_glwe_keyswitch(&extract_input, &mut after_ks, ksk);
+        let keyswitch_bytes = allocated.get() - before;
+
+        let before = allocated.get();
+        for _ in 0..4 {
+            decomposer.decompose_with(&input, &mut output, &mut buffers);
+        }
+        assert!(
+            allocated.get() - before <= 4 * level * keyswitch_bytes,
+            "decompose_with allocated on the heap outside of the keyswitches"
+        );
+        for (j, digit) in output.iter().enumerate() {
+            let expected = ((value >> (j * base_log)) % (1 << base_log)) as i64;
+            assert_eq!(ctx.decrypt_signed(&digit, base_log + 1), expected);
+        }
+    }
+
+    #[test]
+    fn test_decompose_batch() {
+        let mut rng = rand::thread_rng();
+        let (base_log, level) = (4, 3);
+        let n_bits = base_log * level;
+        let mut ctx = new_test_context(
+            DecompositionBaseLog(base_log),
+            DecompositionLevelCount(level),
+        );
+        let values: Vec<u64> = (0..16)
+            .map(|_| rng.r#gen::<u64>() % (1 << n_bits))
+            .collect();
+        let inputs: Vec<_> = values.iter().map(|v| ctx.encrypt(*v, n_bits)).collect();
+
+        let outputs = ctx.decomposer.decompose_batch(&inputs);
+        assert_eq!(outputs.len(), values.len());
+        for (value, output) in values.iter().zip(outputs.iter()) {
+            for (j, digit) in output.iter().enumerate() {
+                let expected = ((value >> (j * base_log)) % (1 << base_log)) as i64;
+                assert_eq!(ctx.decrypt_signed(&digit, base_log + 1), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decompose_slots() {
+        let mut rng = rand::thread_rng();
+        let (base_log, level) = (4, 3);
+        let n_bits = base_log * level;
+        let slot_count = 8;
+        let mut ctx = new_test_context(
+            DecompositionBaseLog(base_log),
+            DecompositionLevelCount(level),
+        );
+        let values: Vec<u64> = (0..slot_count)
+            .map(|_| rng.r#gen::<u64>() % (1 << n_bits))
+            .collect();
+        let input = ctx.encrypt_slots(&values, n_bits);
+
+        let outputs = ctx.decomposer.decompose_slots(&input, slot_count);
+        assert_eq!(outputs.len(), slot_count);
+        for (value, output) in values.iter().zip(outputs.iter()) {
+            for (j, digit) in output.iter().enumerate() {
+                let expected = ((value >> (j * base_log)) % (1 << base_log)) as i64;
+                assert_eq!(ctx.decrypt_signed(&digit, base_log + 1), expected);
+            }
+        }
+    }
+}
@@ -1,30 +1,90 @@
 use crate::algorithms::tools::make_f3_with_b;
 use crate::algorithms::{
-    pbs_many_lut::programmable_bootstrap_lwe_ciphertext_many_lut, tools::make_f1_with_b,
+    pbs_many_lut::{ManyLutBootstrapper, programmable_bootstrap_lwe_ciphertext_many_lut},
+    tools::make_f1_with_b,
 };
 use concrete_fft::c64;
-use refined_tfhe_lhe::{
-    FourierGlweKeyswitchKey, GlweKeyswitchKey, keyswitch_lwe_ciphertext_by_glwe_keyswitch,
-};
+use dyn_stack::PodStack;
+use refined_tfhe_lhe::{FourierGlweKeyswitchKey, keyswitch_lwe_ciphertext_by_glwe_keyswitch};
 use tfhe::boolean::prelude::LweDimension;
-use tfhe::core_crypto::prelude::polynomial_algorithms::{
-    polynomial_karatsuba_wrapping_mul, polynomial_wrapping_mul,
-};
+use tfhe::core_crypto::fft_impl::fft64::math::{fft::FftView, polynomial::FourierPolynomial};
 use tfhe::core_crypto::prelude::{
-    decrypt_glwe_ciphertext, decrypt_lwe_ciphertext, glwe_ciphertext_cleartext_mul_assign, glwe_ciphertext_plaintext_add_assign, glwe_ciphertext_plaintext_sub_assign, lwe_ciphertext_plaintext_add_assign, plaintext_list, programmable_bootstrap_lwe_ciphertext, LweSize, ModulusSwitchOffset, Plaintext, PlaintextList, Polynomial
+    ComputationBuffers, Fft, GlweCiphertextView, LweCiphertextView, LweSize, Plaintext, Polynomial,
+    PolynomialSize, glwe_ciphertext_plaintext_add_assign, glwe_ciphertext_plaintext_sub_assign,
+    lwe_ciphertext_plaintext_add_assign,
 };
-use tfhe::shortint::wopbs::PlaintextCount;
 use tfhe::{
     boolean::prelude::{DecompositionBaseLog, DecompositionLevelCount},
     core_crypto::prelude::{
-        CastFrom, CastInto, Cleartext, Container, ContainerMut, ContiguousEntityContainer,
-        ContiguousEntityContainerMut, FourierLweBootstrapKey, GlweCiphertext, GlweCiphertextCount,
-        GlweCiphertextList, GlweSecretKey, LutCountLog, LweCiphertext, LweKeyswitchKey,
-        MonomialDegree, SignedDecomposer, UnsignedInteger, UnsignedTorus,
-        extract_lwe_sample_from_glwe_ciphertext, glwe_ciphertext_cleartext_mul,
-        glwe_ciphertext_sub_assign, keyswitch_lwe_ciphertext,
+        CastFrom, CastInto, CiphertextModulus, Cleartext, Container, ContainerMut,
+        ContiguousEntityContainer, ContiguousEntityContainerMut, FourierLweBootstrapKey,
+        GlweCiphertext, GlweCiphertextCount, GlweCiphertextList, LutCountLog, LweCiphertext,
+        MonomialDegree, UnsignedInteger, UnsignedTorus, extract_lwe_sample_from_glwe_ciphertext,
+        glwe_ciphertext_cleartext_mul, glwe_ciphertext_cleartext_mul_assign,
+        glwe_ciphertext_sub_assign,
     },
 };
+
+/// Observer of the intermediate ciphertexts of a decomposition.
+///
+/// Production entry points use [`NoTrace`]; key-dependent tracers live behind the
+/// `debug-decrypt` feature.
+pub(crate) trait DecomposeTrace<Scalar: UnsignedInteger> {
+    fn lwe(&self, _label: &str, _lwe: LweCiphertextView<'_, Scalar>) {}
+    fn glwe(&self, _label: &str, _glwe: GlweCiphertextView<'_, Scalar>) {}
+}
+
+pub(crate) struct NoTrace;
+
+impl<Scalar: UnsignedInteger> DecomposeTrace<Scalar> for NoTrace {}
+
+/// Range of the digits produced by the decomposition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigitMode {
+    /// Digits in `[0, 2^base_log)`.
+    Unsigned,
+    /// Digits in `[-2^(base_log - 1), 2^(base_log - 1))`, a digit reaching `2^(base_log - 1)`
+    /// carries into the next level, as `SignedDecomposer` does in plaintext.
+    Balanced,
+}
+
+/// Order of the digits in the output list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigitOrder {
+    /// `output[0]` is the least significant digit, the order in which digits are produced.
+    LsbFirst,
+    /// `output[0]` is the most significant digit, the order of the selector bits in vertical
+    /// packing (see `split_bits_high_to_low` in `fhe_processor`).
+    MsbFirst,
+}
+
+impl DigitOrder {
+    /// Index in the output list of the `j`-th least significant digit out of `level`.
+    pub fn index(self, j: usize, level: usize) -> usize {
+        match self {
+            DigitOrder::LsbFirst => j,
+            DigitOrder::MsbFirst => level - 1 - j,
+        }
+    }
+}
+
+/// Plaintext reference of [`DigitMode::Balanced`] for a `sum(digit_widths)` bit `value`,
+/// least significant digit first.
+pub fn balanced_digits(value: u64, digit_widths: &[usize]) -> Vec<i64> {
+    let mut state = value;
+    let mut digits = Vec::with_capacity(digit_widths.len());
+    for &width in digit_widths {
+        let mut digit = (state & ((1 << width) - 1)) as i64;
+        state >>= width;
+        if digit >= 1 << (width - 1) {
+            digit -= 1 << width;
+            state += 1;
+        }
+        digits.push(digit);
+    }
+    digits
+}
+
 pub fn digit_decompose_no_padding<Scalar, InputCont, OutputCont, BskCont, KskCont>(
     input: &GlweCiphertext<InputCont>,
     output: &mut GlweCiphertextList<OutputCont>,
@@ -32,13 +92,46 @@ pub fn digit_decompose_no_padding<Scalar, InputCont, OutputCont, BskCont, KskCon
     decompose_level: DecompositionLevelCount,
     fourier_bsk: &FourierLweBootstrapKey<BskCont>,
     ksk: &FourierGlweKeyswitchKey<KskCont>,
-    glwe_key: &GlweSecretKey<Vec<Scalar>>, // 需要提供 GlweSecretKey
 ) where
     Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger,
     InputCont: Container<Element = Scalar>,
     OutputCont: ContainerMut<Element = Scalar>,
     BskCont: Container<Element = c64>,
     KskCont: Container<Element = c64>,
+{
+    digit_decompose_no_padding_traced(
+        input,
+        output,
+        decompose_base_log,
+        decompose_level,
+        fourier_bsk,
+        ksk,
+        &NoTrace,
+    );
+}
+
+pub(crate) fn digit_decompose_no_padding_traced<
+    Scalar,
+    InputCont,
+    OutputCont,
+    BskCont,
+    KskCont,
+    Trace,
+>(
+    input: &GlweCiphertext<InputCont>,
+    output: &mut GlweCiphertextList<OutputCont>,
+    decompose_base_log: DecompositionBaseLog,
+    decompose_level: DecompositionLevelCount,
+    fourier_bsk: &FourierLweBootstrapKey<BskCont>,
+    ksk: &FourierGlweKeyswitchKey<KskCont>,
+    trace: &Trace,
+) where
+    Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger,
+    InputCont: Container<Element = Scalar>,
+    OutputCont: ContainerMut<Element = Scalar>,
+    BskCont: Container<Element = c64>,
+    KskCont: Container<Element = c64>,
+    Trace: DecomposeTrace<Scalar>,
 {
     assert!(
         output.glwe_ciphertext_count().0 == decompose_level.0,
@@ -88,6 +181,7 @@ pub fn digit_decompose_no_padding<Scalar, InputCont, OutputCont, BskCont, KskCon
             &mut extract_input,
             MonomialDegree(0),
         );
+        trace.lwe("before f1", extract_input.as_view());
 
         keyswitch_lwe_ciphertext_by_glwe_keyswitch(&extract_input, &mut after_ks, &ksk);
         lwe_ciphertext_plaintext_add_assign(
@@ -108,6 +202,7 @@ pub fn digit_decompose_no_padding<Scalar, InputCont, OutputCont, BskCont, KskCon
             &mut pbs_result.get_mut(0),
             Plaintext(Scalar::ONE << (Scalar::BITS - base_log - 1)),
         );
+        trace.glwe("to be sub", pbs_result.get(0));
 
         glwe_ciphertext_sub_assign(&mut output.get_mut(j + 1), &pbs_result.get(0));
     }
@@ -120,7 +215,6 @@ pub fn digit_decompose_with_padding<Scalar, InputCont, OutputCont, BskCont, KskC
     decompose_level: DecompositionLevelCount,
     fourier_bsk: &FourierLweBootstrapKey<BskCont>,
     ksk: &FourierGlweKeyswitchKey<KskCont>,
-    glwe_key: &GlweSecretKey<Vec<Scalar>>, // 需要提供 GlweSecretKey
 ) where
     Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger,
     InputCont: Container<Element = Scalar>,
@@ -128,161 +222,542 @@ pub fn digit_decompose_with_padding<Scalar, InputCont, OutputCont, BskCont, KskC
     BskCont: Container<Element = c64>,
     KskCont: Container<Element = c64>,
 {
-    // Function implementation goes here
-    assert!(
-        output.glwe_ciphertext_count().0 == decompose_level.0,
-        "The output glwe ciphertext count must be equal to the decomposition level."
+    let luts = LevelLuts::new(
+        &vec![decompose_base_log.0; decompose_level.0],
+        fourier_bsk.polynomial_size(),
     );
-    let fourier_bsk = fourier_bsk.as_view();
-    let ciphertext_modulus = input.ciphertext_modulus();
-    let glwe_lwe_size =
-        LweDimension(ksk.input_glwe_size().to_glwe_dimension().0 * ksk.polynomial_size().0)
-            .to_lwe_size();
-    let after_ks_lwe_size =
-        LweDimension(ksk.output_glwe_size().to_glwe_dimension().0 * ksk.polynomial_size().0)
-            .to_lwe_size();
-    let glwe_size = fourier_bsk.glwe_size();
-    let polynomial_size = fourier_bsk.polynomial_size();
+    digit_decompose_with_padding_traced(
+        input,
+        output,
+        &luts,
+        fourier_bsk,
+        ksk,
+        DigitMode::Unsigned,
+        DigitOrder::LsbFirst,
+        &mut DecomposeBuffers::new(&luts, fourier_bsk, ksk, input.ciphertext_modulus()),
+        &NoTrace,
+    );
+}
 
-    let mut extract_input = LweCiphertext::new(Scalar::ZERO, glwe_lwe_size, ciphertext_modulus);
-    let mut after_ks = LweCiphertext::new(Scalar::ZERO, after_ks_lwe_size, ciphertext_modulus);
+/// Same as [`digit_decompose_with_padding`] with one width per level, least significant first,
+/// e.g. `[4, 4, 3, 1]` for a 12-bit input.
+///
+/// Digit `j` is written to `output[order.index(j, level)]` with its own encoding
+/// `2^(BITS - digit_widths[j] - 1)`. When two neighbouring widths differ, the PBS result is
+/// rescaled by `2^|w_j - w_{j+1}|`, so keep the widths close to avoid amplifying its noise.
+pub fn digit_decompose_with_widths<Scalar, InputCont, OutputCont, BskCont, KskCont>(
+    input: &GlweCiphertext<InputCont>,
+    output: &mut GlweCiphertextList<OutputCont>,
+    digit_widths: &[usize],
+    fourier_bsk: &FourierLweBootstrapKey<BskCont>,
+    ksk: &FourierGlweKeyswitchKey<KskCont>,
+    mode: DigitMode,
+    order: DigitOrder,
+) where
+    Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger,
+    InputCont: Container<Element = Scalar>,
+    OutputCont: ContainerMut<Element = Scalar>,
+    BskCont: Container<Element = c64>,
+    KskCont: Container<Element = c64>,
+{
+    let luts = LevelLuts::new(digit_widths, fourier_bsk.polynomial_size());
+    digit_decompose_with_padding_traced(
+        input,
+        output,
+        &luts,
+        fourier_bsk,
+        ksk,
+        mode,
+        order,
+        &mut DecomposeBuffers::new(&luts, fourier_bsk, ksk, input.ciphertext_modulus()),
+        &NoTrace,
+    );
+}
 
-    let mut pbs_result = GlweCiphertextList::new(
-        Scalar::ZERO,
-        glwe_size,
-        polynomial_size,
-        GlweCiphertextCount(2),
-        ciphertext_modulus,
+/// Balanced counterpart of [`digit_decompose_with_padding`], see [`DigitMode::Balanced`].
+///
+/// Digit `d` is encoded as `d * 2^(BITS - base_log - 1)`, the padding bit carrying its sign.
+pub fn digit_decompose_balanced<Scalar, InputCont, OutputCont, BskCont, KskCont>(
+    input: &GlweCiphertext<InputCont>,
+    output: &mut GlweCiphertextList<OutputCont>,
+    decompose_base_log: DecompositionBaseLog,
+    decompose_level: DecompositionLevelCount,
+    fourier_bsk: &FourierLweBootstrapKey<BskCont>,
+    ksk: &FourierGlweKeyswitchKey<KskCont>,
+) where
+    Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger,
+    InputCont: Container<Element = Scalar>,
+    OutputCont: ContainerMut<Element = Scalar>,
+    BskCont: Container<Element = c64>,
+    KskCont: Container<Element = c64>,
+{
+    let luts = LevelLuts::new(
+        &vec![decompose_base_log.0; decompose_level.0],
+        fourier_bsk.polynomial_size(),
     );
+    digit_decompose_with_padding_traced(
+        input,
+        output,
+        &luts,
+        fourier_bsk,
+        ksk,
+        DigitMode::Balanced,
+        DigitOrder::LsbFirst,
+        &mut DecomposeBuffers::new(&luts, fourier_bsk, ksk, input.ciphertext_modulus()),
+        &NoTrace,
+    );
+}
 
-    let level = decompose_level.0; // 分解层数
-    let k = level - 1;
-    let base_log = decompose_base_log.0; // base_log
-    let lut = vec![make_f1_with_b::<Scalar>(base_log)];
-    let f3 = make_f3_with_b::<Scalar>(base_log);
+/// Scratch ciphertexts and f1 accumulators of a decomposition, allocated once and reused for
+/// every input decomposed with the same keys and digit widths.
+///
+/// Built by [`DigitDecomposer::allocate_buffers`](crate::DigitDecomposer::allocate_buffers).
+pub struct DecomposeBuffers<Scalar: UnsignedTorus> {
+    /// 分配时的各层位数，用来检查缓存的累加器是否还对应当前的 LUT
+    widths: Vec<usize>,
+    extract_input: LweCiphertext<Vec<Scalar>>,
+    after_ks: LweCiphertext<Vec<Scalar>>,
+    pbs_result: GlweCiphertextList<Vec<Scalar>>,
+    temp: GlweCiphertext<Vec<Scalar>>,
+    temp_output: GlweCiphertext<Vec<Scalar>>,
+    shifted_input: GlweCiphertext<Vec<Scalar>>,
+    /// FFT 乘法用的频域多项式和栈
+    fourier: FourierPolynomial<Vec<c64>>,
+    fft_buffers: ComputationBuffers,
+    /// 每一层的 f1 累加器，下标为层号
+    bootstrapper: ManyLutBootstrapper<Scalar>,
+}
 
-    // ---- 1) 只初始化一次：先准备 output[0], output[1] ----
-    let mut temp =
-        GlweCiphertext::new(Scalar::ZERO, glwe_size, polynomial_size, ciphertext_modulus);
-    if level >= 1 {
-        glwe_ciphertext_cleartext_mul(
-            &mut temp,
-            input,
-            Cleartext(Scalar::ONE << ((level - 1) * base_log)),
+impl<Scalar> DecomposeBuffers<Scalar>
+where
+    Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize>,
+{
+    pub(crate) fn new<BskCont, KskCont>(
+        luts: &LevelLuts,
+        fourier_bsk: &FourierLweBootstrapKey<BskCont>,
+        ksk: &FourierGlweKeyswitchKey<KskCont>,
+        ciphertext_modulus: CiphertextModulus<Scalar>,
+    ) -> Self
+    where
+        BskCont: Container<Element = c64>,
+        KskCont: Container<Element = c64>,
+    {
+        luts.assert_fits::<Scalar>();
+        let glwe_lwe_size =
+            LweDimension(ksk.input_glwe_size().to_glwe_dimension().0 * ksk.polynomial_size().0)
+                .to_lwe_size();
+        let after_ks_lwe_size =
+            LweDimension(ksk.output_glwe_size().to_glwe_dimension().0 * ksk.polynomial_size().0)
+                .to_lwe_size();
+        let glwe_size = fourier_bsk.glwe_size();
+        let polynomial_size = fourier_bsk.polynomial_size();
+        let new_glwe =
+            || GlweCiphertext::new(Scalar::ZERO, glwe_size, polynomial_size, ciphertext_modulus);
+
+        let mut bootstrapper = ManyLutBootstrapper::new(fourier_bsk.as_view(), ciphertext_modulus);
+        for lut in luts.levels.iter() {
+            bootstrapper.prepare(
+                &[make_f1_with_b(lut.lut_width)],
+                LutCountLog(0),
+                lut.width - 1,
+            );
+        }
+
+        let fft = Fft::new(polynomial_size);
+        let fft = fft.as_view();
+        let mut fft_buffers = ComputationBuffers::new();
+        fft_buffers.resize(
+            fft.forward_scratch()
+                .unwrap()
+                .or(fft.backward_scratch().unwrap())
+                .unaligned_bytes_required(),
         );
+
+        Self {
+            widths: luts.widths().to_vec(),
+            extract_input: LweCiphertext::new(Scalar::ZERO, glwe_lwe_size, ciphertext_modulus),
+            after_ks: LweCiphertext::new(Scalar::ZERO, after_ks_lwe_size, ciphertext_modulus),
+            pbs_result: GlweCiphertextList::new(
+                Scalar::ZERO,
+                glwe_size,
+                polynomial_size,
+                GlweCiphertextCount(2),
+                ciphertext_modulus,
+            ),
+            temp: new_glwe(),
+            temp_output: new_glwe(),
+            shifted_input: new_glwe(),
+            fourier: FourierPolynomial {
+                data: vec![c64::default(); polynomial_size.to_fourier_polynomial_size().0],
+            },
+            fft_buffers,
+            bootstrapper,
+        }
     }
-    // ---- 2) 主循环：PBS 于 j，上一步结果减到 j+1（不同 j）----
-    for j in 0..=k {
-        // 从 output[j] 抽样 -> KS -> PBS
-        extract_lwe_sample_from_glwe_ciphertext(&temp, &mut extract_input, MonomialDegree(0));
+}
 
-        // let tempp = decrypt_lwe_ciphertext(&glwe_key.as_lwe_secret_key(), &extract_input);
-        // let decomposer =
-        //     SignedDecomposer::<Scalar>::new(decompose_base_log, DecompositionLevelCount(1));
-        // let decode = decomposer.closest_representable(tempp.0);
-        // println!("before f1: {:064b}", decode);
-        keyswitch_lwe_ciphertext_by_glwe_keyswitch(&extract_input, &mut after_ks, &ksk);
+/// LUTs of every level of the padded decomposition, with the f3 multiplier already in the
+/// Fourier domain. They only depend on the digit widths and the polynomial size, so
+/// [`DigitDecomposer`](super::decomposer::DigitDecomposer) builds them once.
+pub(crate) struct LevelLuts {
+    widths: Vec<usize>,
+    /// shifts[j]: 把第 j 段移到最高位所需的左移位数
+    shifts: Vec<usize>,
+    levels: Vec<LevelLut>,
+    fft: Fft,
+}
 
-        // lwe_ciphertext_plaintext_add_assign(
-        //     &mut after_ks,
-        //     Plaintext(Scalar::ONE << (Scalar::BITS - base_log - 1)),
-        // );
-        programmable_bootstrap_lwe_ciphertext_many_lut(
-            &mut after_ks,
-            &mut pbs_result,
-            fourier_bsk,
-            LutCountLog(0),
-            base_log - 1,
-            ciphertext_modulus,
-            &lut,
+impl LevelLuts {
+    pub(crate) fn new(digit_widths: &[usize], polynomial_size: PolynomialSize) -> Self {
+        assert!(!digit_widths.is_empty(), "level must be at least 1");
+        // the f1 accumulator of a level needs one box per value of the digit
+        let max_width = polynomial_size.0.ilog2() as usize;
+        assert!(
+            digit_widths.iter().all(|w| (1..=max_width).contains(w)),
+            "Every digit must be between 1 and log2(polynomial_size) = {max_width} bits wide."
+        );
+        let fft = Fft::new(polynomial_size);
+        let mut buffers = ComputationBuffers::new();
+        buffers.resize(
+            fft.as_view()
+                .forward_scratch()
+                .unwrap()
+                .unaligned_bytes_required(),
         );
 
-        // let decomposer =
-        //     SignedDecomposer::<Scalar>::new(DecompositionBaseLog(8), DecompositionLevelCount(1));
-        // let mut plain_list = PlaintextList::new(Scalar::ZERO, PlaintextCount(polynomial_size.0));
-        // decrypt_glwe_ciphertext(&glwe_key, &pbs_result.get(1), &mut plain_list);
-        // let decode = decomposer.closest_representable(*plain_list.get(0).0);
-        // println!("output is:\t{:064b}", decode);
-
-        let box_size = polynomial_size.0 / (1_usize << (decompose_base_log.0 - 1));
-        let mut accumulator_scalar = vec![Scalar::ZERO; polynomial_size.0];
-        for (i, one_box) in accumulator_scalar.chunks_exact_mut(box_size).enumerate() {
-            let x = Scalar::cast_from(i);
-            // println!("\t{:032b} ", f3.call(x));
-            for a in one_box.iter_mut() {
-                *a = f3.call(x);
-            }
+        // PBS 的结果既用来生成第 j 段（宽度 w_j），又用来从下一段中减去第 j 段（宽度 w_{j+1}），
+        // f1 按两者中较大的宽度生成，再分别用 2 的幂补偿，宽度相同时两边的系数都是 1。
+        let levels = (0..digit_widths.len())
+            .map(|j| {
+                let width = digit_widths[j];
+                let next_width = digit_widths.get(j + 1).copied().unwrap_or(width);
+                LevelLut::new(width, next_width, fft.as_view(), buffers.stack())
+            })
+            .collect();
+        let n_bits: usize = digit_widths.iter().sum();
+        let shifts = digit_widths
+            .iter()
+            .scan(n_bits, |rest, w| {
+                *rest -= w;
+                Some(*rest)
+            })
+            .collect();
+        Self {
+            widths: digit_widths.to_vec(),
+            shifts,
+            levels,
+            fft,
         }
-        let mut temp_acc = accumulator_scalar.clone();
-        temp_acc.rotate_right(1);
-        let end_value = accumulator_scalar[0] + temp_acc[0];
-        for (a,b) in accumulator_scalar.iter_mut().zip(temp_acc.iter()){
-            *a = *a - *b;
+    }
+
+    /// Width of each digit, least significant first.
+    pub(crate) fn widths(&self) -> &[usize] {
+        &self.widths
+    }
+
+    /// Panics unless the digits and their bit of padding fit the `Scalar` torus.
+    pub(crate) fn assert_fits<Scalar: UnsignedInteger>(&self) {
+        assert!(
+            self.widths.iter().sum::<usize>() < Scalar::BITS,
+            "The digits must add up to less than {} bits.",
+            Scalar::BITS
+        );
+    }
+}
+
+pub(crate) fn digit_decompose_with_padding_traced<
+    Scalar,
+    InputCont,
+    OutputCont,
+    BskCont,
+    KskCont,
+    Trace,
+>(
+    input: &GlweCiphertext<InputCont>,
+    output: &mut GlweCiphertextList<OutputCont>,
+    luts: &LevelLuts,
+    fourier_bsk: &FourierLweBootstrapKey<BskCont>,
+    ksk: &FourierGlweKeyswitchKey<KskCont>,
+    mode: DigitMode,
+    order: DigitOrder,
+    buffers: &mut DecomposeBuffers<Scalar>,
+    trace: &Trace,
+) where
+    Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger,
+    InputCont: Container<Element = Scalar>,
+    OutputCont: ContainerMut<Element = Scalar>,
+    BskCont: Container<Element = c64>,
+    KskCont: Container<Element = c64>,
+    Trace: DecomposeTrace<Scalar>,
+{
+    let digit_widths = luts.widths();
+    assert!(
+        output.glwe_ciphertext_count().0 == digit_widths.len(),
+        "The output glwe ciphertext count must be equal to the decomposition level."
+    );
+    luts.assert_fits::<Scalar>();
+    let level = digit_widths.len(); // 分解层数
+    let k = level - 1;
+    let shifts = luts.shifts.as_slice();
+
+    let DecomposeBuffers {
+        widths,
+        extract_input,
+        after_ks,
+        pbs_result,
+        temp,
+        temp_output,
+        shifted_input,
+        fourier,
+        fft_buffers,
+        bootstrapper,
+    } = buffers;
+    assert_eq!(
+        widths.as_slice(),
+        digit_widths,
+        "the buffers must be allocated for the same digit widths"
+    );
+    let fft = luts.fft.as_view();
+    let mut stack = fft_buffers.stack();
+
+    // Balanced digits: adding B/2 to every level beforehand turns the unsigned digits of the
+    // shifted value into the balanced digits plus B/2, the carries being propagated by the
+    // unsigned decomposition itself. B/2 is removed from every digit at the end.
+    let input = match mode {
+        DigitMode::Unsigned => input.as_view(),
+        DigitMode::Balanced => {
+            let offset = shifts.iter().fold(Scalar::ZERO, |acc, shift| {
+                acc.wrapping_add(Scalar::ONE << (Scalar::BITS - shift - 1))
+            });
+            shifted_input.as_mut().copy_from_slice(input.as_ref());
+            glwe_ciphertext_plaintext_add_assign(shifted_input, Plaintext(offset));
+            shifted_input.as_view()
         }
-        accumulator_scalar[0] = end_value;
+    };
+
+    let fourier_bsk = fourier_bsk.as_view();
+
+    // ---- 1) 只初始化一次：先准备 output[0], output[1] ----
+    glwe_ciphertext_cleartext_mul(temp, &input, Cleartext(Scalar::ONE << shifts[0]));
+    // ---- 2) 主循环：PBS 于 j，上一步结果减到 j+1（不同 j）----
+    for j in 0..=k {
+        let lut = &luts.levels[j];
+        // 从 output[j] 抽样 -> KS -> PBS
+        extract_lwe_sample_from_glwe_ciphertext(temp, extract_input, MonomialDegree(0));
+        trace.lwe("before f1", extract_input.as_view());
 
-        let prod = Polynomial::from_container(accumulator_scalar);
+        keyswitch_lwe_ciphertext_by_glwe_keyswitch(extract_input, after_ks, &ksk);
 
-        // let decomposer =
-        //     SignedDecomposer::<Scalar>::new(DecompositionBaseLog(16), DecompositionLevelCount(1));
-        // let mut plain_list = PlaintextList::new(Scalar::ZERO, PlaintextCount(polynomial_size.0));
-        // decrypt_glwe_ciphertext(&glwe_key, &pbs_result.get(0), &mut plain_list);
-        // let decode = decomposer.closest_representable(*plain_list.get(0).0);
-        // println!("before prod:\t{:064b}", decode);
+        bootstrapper.bootstrap_into(j, after_ks, pbs_result, fourier_bsk);
+        trace.glwe("before prod", pbs_result.get(0));
 
-        let mut temp_output =
-            GlweCiphertext::new(Scalar::ZERO, glwe_size, polynomial_size, ciphertext_modulus);
-        for (mut result, input) in temp_output
+        // 每个分量在频域里乘上缓存好的乘子
+        for (result, input) in temp_output
             .as_mut_polynomial_list()
             .iter_mut()
             .zip(pbs_result.get(0).as_polynomial_list().iter())
         {
-            polynomial_wrapping_mul(&mut result, &input, &prod);
+            fft.forward_as_torus(fourier.as_mut_view(), input, stack.rb_mut());
+            for (a, b) in fourier.data.iter_mut().zip(lut.prod.data.iter()) {
+                *a *= *b;
+            }
+            fft.backward_as_torus(result, fourier.as_view(), stack.rb_mut());
         }
-        
-        // decrypt_glwe_ciphertext(&glwe_key, &temp_output, &mut plain_list);
-        // let decode = decomposer.closest_representable(*plain_list.get(0).0);
-        // println!("before add:\t{:064b}", decode);
+        trace.glwe("before add", temp_output.as_view());
 
         glwe_ciphertext_plaintext_add_assign(
-            &mut temp_output,
+            temp_output,
             Plaintext(
-                (Scalar::ONE << (Scalar::BITS - base_log - 2))
-                    * ((Scalar::ONE << base_log) - Scalar::ONE),
+                (Scalar::ONE << (Scalar::BITS - lut.width - 2))
+                    * ((Scalar::ONE << lut.width) - Scalar::ONE),
             ),
         );
+        if mode == DigitMode::Balanced {
+            glwe_ciphertext_plaintext_sub_assign(
+                temp_output,
+                Plaintext(Scalar::ONE << (Scalar::BITS - 2)),
+            );
+        }
 
-
-        let mut dst = output.get_mut(j);
+        let out_index = order.index(j, level);
+        let mut dst = output.get_mut(out_index);
         dst.as_mut().copy_from_slice(temp_output.as_ref());
+        trace.glwe("digit", output.get(out_index));
 
-        // let decomposer =
-        //     SignedDecomposer::<Scalar>::new(DecompositionBaseLog(8), DecompositionLevelCount(1));
-        // let mut plain_list = PlaintextList::new(Scalar::ZERO, PlaintextCount(polynomial_size.0));
-        // decrypt_glwe_ciphertext(&glwe_key, &pbs_result.get(0), &mut plain_list);
-        // let decode = decomposer.closest_representable(*plain_list.get(0).0);
-        // println!("before add:\t{:064b}", decode);
         if j == k {
             break;
         }
+        if lut.sub_scale_log > 0 {
+            glwe_ciphertext_cleartext_mul_assign(
+                &mut pbs_result.get_mut(0),
+                Cleartext(Scalar::ONE << lut.sub_scale_log),
+            );
+        }
         glwe_ciphertext_plaintext_add_assign(
             &mut pbs_result.get_mut(0),
-            Plaintext(Scalar::ONE << (Scalar::BITS - base_log - 1)),
+            Plaintext(Scalar::ONE << (Scalar::BITS - lut.next_width - 1)),
         );
-        // println!("added:\t\t\t{:064b}", Scalar::ONE << (Scalar::BITS -  base_log - 2));
-        // let decomposer =
-        //     SignedDecomposer::<Scalar>::new(DecompositionBaseLog(8), DecompositionLevelCount(1));
-        // let mut plain_list = PlaintextList::new(Scalar::ZERO, PlaintextCount(polynomial_size.0));
-        // decrypt_glwe_ciphertext(&glwe_key, &pbs_result.get(0), &mut plain_list);
-        // let decode = decomposer.closest_representable(*plain_list.get(0).0);
-        // println!("to be sub:\t{:064b}", decode);
+        trace.glwe("to be sub", pbs_result.get(0));
 
-        glwe_ciphertext_cleartext_mul(
-            &mut temp,
-            input,
-            Cleartext(Scalar::ONE << ((level - (j + 2)) * base_log)),
-        );
+        glwe_ciphertext_cleartext_mul(temp, &input, Cleartext(Scalar::ONE << shifts[j + 1]));
         // 用 PBS 结果“减到下一段”：minuend = output[j+1], subtrahend = pbs_result[0]
-        glwe_ciphertext_sub_assign(&mut temp, &pbs_result.get(0));
+        glwe_ciphertext_sub_assign(temp, &pbs_result.get(0));
+    }
+}
+
+/// LUTs of one level of [`digit_decompose_with_padding_traced`].
+struct LevelLut {
+    width: usize,
+    next_width: usize,
+    /// f1 的幅度：max(width, next_width)
+    lut_width: usize,
+    /// f3 的盒子做差得到的乘子（频域），已乘上 2^(max - width)
+    prod: FourierPolynomial<Vec<c64>>,
+    /// 减到下一段之前 PBS 结果需要乘的 2 的幂：max - next_width
+    sub_scale_log: usize,
+}
+
+impl LevelLut {
+    fn new(width: usize, next_width: usize, fft: FftView<'_>, stack: PodStack<'_>) -> Self {
+        let polynomial_size = fft.polynomial_size();
+        let lut_width = width.max(next_width);
+        let accumulator_scalar = f3_multiplier(width, next_width, polynomial_size);
+
+        let mut prod = FourierPolynomial {
+            data: vec![c64::default(); polynomial_size.to_fourier_polynomial_size().0],
+        };
+        fft.forward_as_integer(
+            prod.as_mut_view(),
+            Polynomial::from_container(accumulator_scalar.as_slice()),
+            stack,
+        );
+
+        Self {
+            width,
+            next_width,
+            lut_width,
+            prod,
+            sub_scale_log: lut_width - next_width,
+        }
+    }
+}
+
+/// f3 的盒子做差得到的乘子 A(1 - X)，系数都是小整数（按有符号数解释）。
+fn f3_multiplier(width: usize, next_width: usize, polynomial_size: PolynomialSize) -> Vec<u64> {
+    let f3 = make_f3_with_b::<u64>(width);
+    let digit_scale = 1_u64 << (width.max(next_width) - width);
+
+    let box_size = polynomial_size.0 / (1_usize << (width - 1));
+    let mut accumulator_scalar = vec![0_u64; polynomial_size.0];
+    for (i, one_box) in accumulator_scalar.chunks_exact_mut(box_size).enumerate() {
+        let x = i as u64;
+        for a in one_box.iter_mut() {
+            *a = f3.call(x).wrapping_mul(digit_scale);
+        }
+    }
+    let mut temp_acc = accumulator_scalar.clone();
+    temp_acc.rotate_right(1);
+    let end_value = accumulator_scalar[0].wrapping_add(temp_acc[0]);
+    for (a, b) in accumulator_scalar.iter_mut().zip(temp_acc.iter()) {
+        *a = (*a).wrapping_sub(*b);
+    }
+    accumulator_scalar[0] = end_value;
+    accumulator_scalar
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use tfhe::core_crypto::prelude::SignedDecomposer;
+    use tfhe::core_crypto::prelude::polynomial_algorithms::polynomial_wrapping_mul;
+
+    use super::*;
+
+    #[test]
+    fn test_balanced_digits_match_signed_decomposer() {
+        let mut rng = rand::thread_rng();
+        for base_log in 2..=6 {
+            for level in 1..=5 {
+                let n_bits = base_log * level;
+                let half = 1_i64 << (base_log - 1);
+                let decomposer = SignedDecomposer::<u64>::new(
+                    DecompositionBaseLog(base_log),
+                    DecompositionLevelCount(level),
+                );
+                for _ in 0..1000 {
+                    let value = rng.r#gen::<u64>() % (1 << n_bits);
+                    let digits = balanced_digits(value, &vec![base_log; level]);
+                    assert!(digits.iter().all(|d| -half <= *d && *d < half));
+
+                    let recomposed = digits
+                        .iter()
+                        .rev()
+                        .fold(0_i64, |acc, d| (acc << base_log) + d);
+                    assert_eq!(recomposed.rem_euclid(1 << n_bits) as u64, value);
+
+                    // level 1 of SignedDecomposer is the most significant digit
+                    let mut expected = vec![0_i64; level];
+                    for term in decomposer.decompose(value << (64 - n_bits)) {
+                        expected[level - term.level().0] = term.value() as i64;
+                    }
+                    // SignedDecomposer may keep +B/2 instead of carrying, otherwise the balanced
+                    // representation is unique
+                    if expected.iter().all(|d| *d < half) {
+                        assert_eq!(digits, expected);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "log2(polynomial_size) = 11")]
+    fn test_level_luts_reject_wide_digit() {
+        LevelLuts::new(&[4, 12], PolynomialSize(2048));
+    }
+
+    #[test]
+    fn test_fourier_prod_matches_wrapping_mul() {
+        let mut rng = rand::thread_rng();
+        let polynomial_size = PolynomialSize(2048);
+        let widths = [4, 4, 3, 1, 2];
+        let luts = LevelLuts::new(&widths, polynomial_size);
+        let fft = luts.fft.as_view();
+        let mut buffers = ComputationBuffers::new();
+        buffers.resize(
+            fft.forward_scratch()
+                .unwrap()
+                .or(fft.backward_scratch().unwrap())
+                .unaligned_bytes_required(),
+        );
+        let mut stack = buffers.stack();
+
+        for (j, lut) in luts.levels.iter().enumerate() {
+            let next_width = widths.get(j + 1).copied().unwrap_or(widths[j]);
+            let multiplier =
+                Polynomial::from_container(f3_multiplier(widths[j], next_width, polynomial_size));
+            let input = Polynomial::from_container(
+                (0..polynomial_size.0)
+                    .map(|_| rng.r#gen::<u64>())
+                    .collect::<Vec<_>>(),
+            );
+            let mut expected = Polynomial::new(0_u64, polynomial_size);
+            polynomial_wrapping_mul(&mut expected, &input, &multiplier);
+
+            let mut fourier = FourierPolynomial {
+                data: vec![c64::default(); polynomial_size.to_fourier_polynomial_size().0],
+            };
+            fft.forward_as_torus(fourier.as_mut_view(), input.as_view(), stack.rb_mut());
+            for (a, b) in fourier.data.iter_mut().zip(lut.prod.data.iter()) {
+                *a *= *b;
+            }
+            let mut result = Polynomial::new(0_u64, polynomial_size);
+            fft.backward_as_torus(result.as_mut_view(), fourier.as_view(), stack.rb_mut());
+
+            // FFT 的误差只落在低位
+            for (r, e) in result.as_ref().iter().zip(expected.as_ref().iter()) {
+                assert!((r.wrapping_sub(*e) as i64).unsigned_abs() < 1 << 20);
+            }
+        }
     }
 }
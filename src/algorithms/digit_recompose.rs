@@ -0,0 +1,235 @@
+//! Inverse of the digit decomposition: every digit is bootstrapped to its weight in the large
+//! plaintext and the results are summed into a single LWE.
+use fhe_processor::{
+    processors::{
+        key_gen::allocate_and_generate_new_reused_lwe_key,
+        lwe_storede_ks::stored_reused_keyswitch_lwe_ciphertext,
+    },
+    utils::parms::ProcessorParam,
+};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use tfhe::core_crypto::prelude::{
+    ActivatedRandomGenerator, Container, ContiguousEntityContainer, DecompositionBaseLog,
+    DecompositionLevelCount, EncryptionRandomGenerator, FourierLweBootstrapKey,
+    FourierLweBootstrapKeyOwned, GlweCiphertextList, GlweSecretKey, LweCiphertext, LweSize,
+    MonomialDegree, allocate_and_generate_new_lwe_bootstrap_key,
+    convert_standard_lwe_bootstrap_key_to_fourier, extract_lwe_sample_from_glwe_ciphertext,
+    generate_programmable_bootstrap_glwe_lut, lwe_ciphertext_add_assign,
+    programmable_bootstrap_lwe_ciphertext,
+};
+
+use crate::algorithms::{cbs_with_decompose::CbsKeys, digit_decompose::DigitOrder};
+
+/// Bootstrapping key of the recomposition, from the small LWE key of the circuit bootstrapping
+/// to the GLWE key of the table lookups.
+pub struct RecomposeKey {
+    fourier_bsk: FourierLweBootstrapKeyOwned,
+}
+
+impl RecomposeKey {
+    pub fn new<KeyCont>(
+        glwe_key: &GlweSecretKey<KeyCont>,
+        params: &ProcessorParam<u64>,
+        base_log: DecompositionBaseLog,
+        level: DecompositionLevelCount,
+        encryption_generator: &mut EncryptionRandomGenerator<ActivatedRandomGenerator>,
+    ) -> Self
+    where
+        KeyCont: Container<Element = u64>,
+    {
+        let glwe_lwe_sk = glwe_key.as_lwe_secret_key();
+        let cbs_glwe_key =
+            GlweSecretKey::from_container(glwe_key.as_ref().to_vec(), params.polynomial_size());
+        let lwe_sk_after_ks =
+            allocate_and_generate_new_reused_lwe_key(&glwe_lwe_sk, params.lwe_dimension());
+        let bsk = allocate_and_generate_new_lwe_bootstrap_key(
+            &lwe_sk_after_ks,
+            &cbs_glwe_key,
+            base_log,
+            level,
+            params.glwe_modular_std_dev(),
+            params.ciphertext_modulus(),
+            encryption_generator,
+        );
+        let mut fourier_bsk = FourierLweBootstrapKey::new(
+            bsk.input_lwe_dimension(),
+            bsk.glwe_size(),
+            bsk.polynomial_size(),
+            bsk.decomposition_base_log(),
+            bsk.decomposition_level_count(),
+        );
+        convert_standard_lwe_bootstrap_key_to_fourier(&bsk, &mut fourier_bsk);
+        Self { fourier_bsk }
+    }
+
+    /// Key with the [`recompose_parameters`] of a `level`-digit recomposition.
+    pub fn for_level<KeyCont>(
+        glwe_key: &GlweSecretKey<KeyCont>,
+        params: &ProcessorParam<u64>,
+        level: DecompositionLevelCount,
+        encryption_generator: &mut EncryptionRandomGenerator<ActivatedRandomGenerator>,
+    ) -> Self
+    where
+        KeyCont: Container<Element = u64>,
+    {
+        let (base_log, bsk_level) = recompose_parameters(level);
+        Self::new(glwe_key, params, base_log, bsk_level, encryption_generator)
+    }
+}
+
+/// Decomposition parameters of the recomposition bootstrapping key for `level` digits.
+///
+/// The output precision grows with the number of digits, so the key gets finer with the level.
+pub fn recompose_parameters(
+    level: DecompositionLevelCount,
+) -> (DecompositionBaseLog, DecompositionLevelCount) {
+    match level.0 {
+        ..=2 => (DecompositionBaseLog(24), DecompositionLevelCount(1)),
+        3 => (DecompositionBaseLog(16), DecompositionLevelCount(2)),
+        4 => (DecompositionBaseLog(12), DecompositionLevelCount(3)),
+        _ => (DecompositionBaseLog(10), DecompositionLevelCount(4)),
+    }
+}
+
+/// Recompose the digits of a [`DigitDecomposer`](super::decomposer::DigitDecomposer) into a
+/// single LWE.
+///
+/// See [`digit_recompose_lwes`], the constant coefficient of every GLWE being the digit.
+pub fn digit_recompose<DigitCont>(
+    digits: &GlweCiphertextList<DigitCont>,
+    digit_widths: &[usize],
+    order: DigitOrder,
+    keys: &CbsKeys,
+    recompose_key: &RecomposeKey,
+) -> LweCiphertext<Vec<u64>>
+where
+    DigitCont: Container<Element = u64>,
+{
+    let lwe_size =
+        LweSize(digits.glwe_size().to_glwe_dimension().0 * digits.polynomial_size().0 + 1);
+    let lwes: Vec<_> = digits
+        .iter()
+        .map(|digit| {
+            let mut lwe = LweCiphertext::new(0u64, lwe_size, digits.ciphertext_modulus());
+            extract_lwe_sample_from_glwe_ciphertext(&digit, &mut lwe, MonomialDegree(0));
+            lwe
+        })
+        .collect();
+    digit_recompose_lwes(&lwes, digit_widths, order, keys, recompose_key)
+}
+
+/// Recompose unsigned digits into the `n_bits = sum(digit_widths)` bit value they represent,
+/// encoded at `2^(64 - n_bits)` like the input of the decomposition.
+///
+/// `digit_widths` lists the widths from the least significant digit, and `order` tells where
+/// each digit is in `digits`. Every digit is encoded on its width with one bit of padding, as
+/// produced by a [`DigitDecomposer`](super::decomposer::DigitDecomposer) in
+/// [`DigitMode::Unsigned`](super::digit_decompose::DigitMode::Unsigned) or by a table lookup.
+pub fn digit_recompose_lwes<DigitCont>(
+    digits: &[LweCiphertext<DigitCont>],
+    digit_widths: &[usize],
+    order: DigitOrder,
+    keys: &CbsKeys,
+    recompose_key: &RecomposeKey,
+) -> LweCiphertext<Vec<u64>>
+where
+    DigitCont: Container<Element = u64> + Sync,
+{
+    let level = digit_widths.len();
+    assert_eq!(digits.len(), level, "one digit is needed per width");
+    let n_bits: usize = digit_widths.iter().sum();
+    assert!(
+        n_bits < u64::BITS as usize,
+        "the recomposed value must fit in 63 bits"
+    );
+
+    let params = keys.params();
+    let ciphertext_modulus = params.ciphertext_modulus();
+    let glwe_size = params.glwe_dimension().to_glwe_size();
+    let polynomial_size = params.polynomial_size();
+    let output_lwe_size = keys.output_lwe_size();
+
+    let weighted: Vec<_> = (0..level)
+        .into_par_iter()
+        .map(|j| {
+            let digit = &digits[order.index(j, level)];
+            let width = digit_widths[j];
+            let offset: usize = digit_widths[..j].iter().sum();
+            let accumulator = generate_programmable_bootstrap_glwe_lut(
+                polynomial_size,
+                glwe_size,
+                1 << width,
+                ciphertext_modulus,
+                1u64 << (u64::BITS as usize - n_bits + offset),
+                |d| d,
+            );
+            let mut small_lwe =
+                LweCiphertext::new(0u64, keys.ksk().output_lwe_size(), ciphertext_modulus);
+            stored_reused_keyswitch_lwe_ciphertext(keys.ksk(), digit, &mut small_lwe);
+            let mut output = LweCiphertext::new(0u64, output_lwe_size, ciphertext_modulus);
+            programmable_bootstrap_lwe_ciphertext(
+                &small_lwe,
+                &mut output,
+                &accumulator,
+                &recompose_key.fourier_bsk,
+            );
+            output
+        })
+        .collect();
+
+    let mut result = LweCiphertext::new(0u64, output_lwe_size, ciphertext_modulus);
+    for output in weighted.iter() {
+        lwe_ciphertext_add_assign(&mut result, output);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use fhe_processor::utils::instance::SetI;
+    use rand::Rng;
+    use tfhe::core_crypto::prelude::{SignedDecomposer, decrypt_lwe_ciphertext};
+
+    use super::*;
+    use crate::algorithms::test_utils::new_test_context;
+
+    #[test]
+    fn test_decompose_then_recompose() {
+        let mut rng = rand::thread_rng();
+        let base_log = 4;
+        let mut ctx = new_test_context(DecompositionBaseLog(base_log), DecompositionLevelCount(2));
+        let keys = CbsKeys::new(&ctx.glwe_key, *SetI, &mut ctx.encryption_generator);
+
+        for level in 2..=5 {
+            let n_bits = base_log * level;
+            let level = DecompositionLevelCount(level);
+            let recompose_key = RecomposeKey::for_level(
+                &ctx.glwe_key,
+                keys.params(),
+                level,
+                &mut ctx.encryption_generator,
+            );
+            ctx.decomposer.set_level_count(level);
+            let decoder = SignedDecomposer::<u64>::new(
+                DecompositionBaseLog(n_bits),
+                DecompositionLevelCount(1),
+            );
+            for order in [DigitOrder::LsbFirst, DigitOrder::MsbFirst] {
+                ctx.decomposer.set_order(order);
+                let value = rng.r#gen::<u64>() % (1 << n_bits);
+                let input = ctx.encrypt(value, n_bits);
+                let digits = ctx.decomposer.decompose(&input);
+                let output = digit_recompose(
+                    &digits,
+                    ctx.decomposer.digit_widths(),
+                    order,
+                    &keys,
+                    &recompose_key,
+                );
+                let plain = decrypt_lwe_ciphertext(&ctx.glwe_key.as_lwe_secret_key(), &output);
+                let result = decoder.closest_representable(plain.0) >> (64 - n_bits);
+                assert_eq!(result, value, "level {} order {order:?}", level.0);
+            }
+        }
+    }
+}
@@ -1,4 +1,10 @@
+pub mod decomposer;
 pub mod digit_decompose;
+pub mod digit_recompose;
 pub mod pbs_many_lut;
 pub mod cbs_with_decompose;
-mod tools;
\ No newline at end of file
+#[cfg(feature = "debug-decrypt")]
+pub mod debug_decrypt;
+pub mod tools;
+#[cfg(test)]
+mod test_utils;
@@ -21,6 +21,11 @@ use tfhe::{
 
 use crate::algorithms::tools::LutFn;
 
+/// Many-LUT bootstrapping of `lwe_in`: `out_list[i]` receives `f[i % f.len()]` evaluated on the
+/// message of `message_modulus_log` bits.
+///
+/// Allocates the FFT plan, the scratch stack and the accumulator on every call, use a
+/// [`ManyLutBootstrapper`] in loops.
 pub fn programmable_bootstrap_lwe_ciphertext_many_lut<Scalar, InputCont, OutputCont>(
     lwe_in: &LweCiphertext<InputCont>,
     out_list: &mut GlweCiphertextList<OutputCont>,
@@ -28,86 +33,405 @@ pub fn programmable_bootstrap_lwe_ciphertext_many_lut<Scalar, InputCont, OutputC
     log_lut_count: LutCountLog,
     message_modulus_log: usize,
     ciphertext_modulus: CiphertextModulus<Scalar>,
-    f: &[LutFn],
+    f: &[LutFn<Scalar>],
 ) where
     Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger,
     InputCont: Container<Element = Scalar>,
     OutputCont: ContainerMut<Element = Scalar>,
 {
-    assert_eq!(
-        lwe_in.lwe_size(),
-        fourier_bsk.input_lwe_dimension().to_lwe_size()
-    );
-    assert_eq!(out_list.glwe_size(), fourier_bsk.glwe_size());
-    assert_eq!(out_list.polynomial_size(), fourier_bsk.polynomial_size());
-    assert!(
-        f.len() <= 1 << log_lut_count.0,
-        "f.len() must be less than or equal to 2^log_lut_count"
-    );
-
-    let polynomial_size = fourier_bsk.polynomial_size();
-    let glwe_size = fourier_bsk.glwe_size();
-
-    let half_box_size = polynomial_size.0 / (2_usize << message_modulus_log);
-    let box_size = 2 * half_box_size;
-
-    let mut accumulator_scalar = vec![Scalar::ZERO; polynomial_size.0];
-    for (i, one_box) in accumulator_scalar.chunks_exact_mut(box_size).enumerate() {
-        let x = Scalar::cast_from(i);
-        for (p, a) in one_box.iter_mut().enumerate() {
-            let func_idx = p % f.len(); // 在 box 内循环使用 f[0], f[1], f[2], ...
-            *a = f[func_idx].call(x); // 形如 f0(i), f1(i), f0(i), f1(i), ...
+    let mut bootstrapper = ManyLutBootstrapper::new(fourier_bsk, ciphertext_modulus);
+    let lut = bootstrapper.prepare(f, log_lut_count, message_modulus_log);
+    bootstrapper.bootstrap_into(lut, lwe_in, out_list, fourier_bsk);
+}
+
+/// Reusable many-LUT bootstrapping.
+///
+/// Holds the FFT plan, the scratch stack and the accumulators of every prepared set of
+/// [`LutFn`], so that [`ManyLutBootstrapper::bootstrap_into`] does no heap allocation.
+pub struct ManyLutBootstrapper<Scalar: UnsignedTorus> {
+    glwe_size: GlweSize,
+    polynomial_size: PolynomialSize,
+    ciphertext_modulus: CiphertextModulus<Scalar>,
+    fft: Fft,
+    buffers: ComputationBuffers,
+    luts: Vec<PreparedLut<Scalar>>,
+}
+
+struct PreparedLut<Scalar: UnsignedTorus> {
+    accumulator: GlweCiphertext<Vec<Scalar>>,
+    log_lut_count: LutCountLog,
+}
+
+impl<Scalar> ManyLutBootstrapper<Scalar>
+where
+    Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize>,
+{
+    /// Bootstrapper for the output GLWE parameters of `fourier_bsk`, without any prepared LUT.
+    pub fn new(
+        fourier_bsk: FourierLweBootstrapKeyView,
+        ciphertext_modulus: CiphertextModulus<Scalar>,
+    ) -> Self {
+        let glwe_size = fourier_bsk.glwe_size();
+        let polynomial_size = fourier_bsk.polynomial_size();
+        let fft = Fft::new(polynomial_size);
+        let mut buffers = ComputationBuffers::new();
+        buffers.resize(
+            programmable_bootstrap_lwe_ciphertext_mem_optimized_requirement::<Scalar>(
+                glwe_size,
+                polynomial_size,
+                fft.as_view(),
+            )
+            .unwrap()
+            .unaligned_bytes_required(),
+        );
+        Self {
+            glwe_size,
+            polynomial_size,
+            ciphertext_modulus,
+            fft,
+            buffers,
+            luts: Vec::new(),
+        }
+    }
+
+    /// Precompute the accumulator evaluating `f` on messages of `message_modulus_log` bits with
+    /// `2^log_lut_count` outputs, returning its index for [`ManyLutBootstrapper::bootstrap_into`].
+    pub fn prepare(
+        &mut self,
+        f: &[LutFn<Scalar>],
+        log_lut_count: LutCountLog,
+        message_modulus_log: usize,
+    ) -> usize {
+        assert!(
+            f.len() <= 1 << log_lut_count.0,
+            "f.len() must be less than or equal to 2^log_lut_count"
+        );
+        let polynomial_size = self.polynomial_size;
+
+        let half_box_size = polynomial_size.0 / (2_usize << message_modulus_log);
+        let box_size = 2 * half_box_size;
+        assert!(
+            half_box_size >= 1,
+            "message_modulus_log must be less than log2(polynomial_size)"
+        );
+        let box_count = polynomial_size.0 / box_size;
+        assert!(
+            f.iter()
+                .filter_map(LutFn::table_len)
+                .all(|len| len == box_count),
+            "a LutFn table needs one entry per box, 2^message_modulus_log = {box_count}"
+        );
+
+        let mut accumulator_scalar = vec![Scalar::ZERO; polynomial_size.0];
+        for (i, one_box) in accumulator_scalar.chunks_exact_mut(box_size).enumerate() {
+            let x = Scalar::cast_from(i);
+            for (p, a) in one_box.iter_mut().enumerate() {
+                let func_idx = p % f.len(); // 在 box 内循环使用 f[0], f[1], f[2], ...
+                *a = f[func_idx].call(x); // 形如 f0(i), f1(i), f0(i), f1(i), ...
+            }
+        }
+
+        for a_i in accumulator_scalar[0..half_box_size].iter_mut() {
+            *a_i = (*a_i).wrapping_neg();
         }
+        accumulator_scalar.rotate_left(half_box_size);
+
+        let accumulator_plaintext = PlaintextList::from_container(accumulator_scalar);
+        let accumulator = allocate_and_trivially_encrypt_new_glwe_ciphertext(
+            self.glwe_size,
+            &accumulator_plaintext,
+            self.ciphertext_modulus,
+        );
+        self.luts.push(PreparedLut {
+            accumulator,
+            log_lut_count,
+        });
+        self.luts.len() - 1
     }
-    // for e in accumulator_scalar.iter().skip(1).step_by(box_size){
-    //     println!("{:064b}", e);
-    // }
 
-    for a_i in accumulator_scalar[0..half_box_size].iter_mut() {
-        *a_i = (*a_i).wrapping_neg();
+    /// Number of prepared accumulators.
+    pub fn lut_count(&self) -> usize {
+        self.luts.len()
     }
-    accumulator_scalar.rotate_left(half_box_size);
 
-    let accumulator_plaintext = PlaintextList::from_container(accumulator_scalar);
-    let accumulator = allocate_and_trivially_encrypt_new_glwe_ciphertext(
-        glwe_size,
-        &accumulator_plaintext,
-        ciphertext_modulus,
-    );
+    /// Bootstrap `lwe_in` with the accumulator `lut`, `out_list[i]` receiving the `i`-th output.
+    pub fn bootstrap_into<InputCont, OutputCont>(
+        &mut self,
+        lut: usize,
+        lwe_in: &LweCiphertext<InputCont>,
+        out_list: &mut GlweCiphertextList<OutputCont>,
+        fourier_bsk: FourierLweBootstrapKeyView,
+    ) where
+        InputCont: Container<Element = Scalar>,
+        OutputCont: ContainerMut<Element = Scalar>,
+    {
+        assert_eq!(out_list.glwe_size(), self.glwe_size);
+        assert_eq!(out_list.polynomial_size(), self.polynomial_size);
 
-    let mut buffers = ComputationBuffers::new();
-    let fft = Fft::new(polynomial_size);
-    let fft = fft.as_view();
+        self.blind_rotate(lut, lwe_in, fourier_bsk, |accumulator| {
+            for (i, mut glwe) in out_list.iter_mut().enumerate() {
+                glwe_ciphertext_monic_monomial_div(&mut glwe, &accumulator, MonomialDegree(i));
+            }
+        });
+    }
 
-    buffers.resize(
-        programmable_bootstrap_lwe_ciphertext_mem_optimized_requirement::<Scalar>(
-            glwe_size,
-            polynomial_size,
-            fft,
-        )
-        .unwrap()
-        .unaligned_bytes_required(),
-    );
-    let stack = buffers.stack();
-
-    let (mut local_accumulator_data, stack) =
-        stack.collect_aligned(CACHELINE_ALIGN, accumulator.as_ref().iter().copied());
-    let mut local_accumulator = GlweCiphertextMutView::from_container(
-        &mut *local_accumulator_data,
-        polynomial_size,
-        ciphertext_modulus,
-    );
-
-    gen_blind_rotate_local_assign(
-        fourier_bsk,
-        local_accumulator.as_mut_view(),
-        ModulusSwitchOffset(0),
-        log_lut_count,
-        lwe_in.as_ref(),
-        fft,
-        stack,
-    );
-    for (i, mut glwe) in out_list.iter_mut().enumerate() {
-        glwe_ciphertext_monic_monomial_div(&mut glwe, &local_accumulator, MonomialDegree(i));
+    /// Same as [`ManyLutBootstrapper::bootstrap_into`], sample-extracting the `i`-th output
+    /// straight from the rotated accumulator into `outputs[i]` instead of building GLWEs.
+    pub fn bootstrap_into_lwes<InputCont, OutputCont>(
+        &mut self,
+        lut: usize,
+        lwe_in: &LweCiphertext<InputCont>,
+        outputs: &mut [LweCiphertext<OutputCont>],
+        fourier_bsk: FourierLweBootstrapKeyView,
+    ) where
+        InputCont: Container<Element = Scalar>,
+        OutputCont: ContainerMut<Element = Scalar>,
+    {
+        assert!(
+            outputs.len() <= 1 << self.luts[lut].log_lut_count.0,
+            "outputs.len() must be less than or equal to 2^log_lut_count"
+        );
+
+        self.blind_rotate(lut, lwe_in, fourier_bsk, |accumulator| {
+            for (i, lwe) in outputs.iter_mut().enumerate() {
+                // 第 i 个输出是 X^(-i) * ACC 的常数项，即 ACC 的第 i 个系数
+                extract_lwe_sample_from_glwe_ciphertext(&accumulator, lwe, MonomialDegree(i));
+            }
+        });
+    }
+
+    /// Blind-rotate a copy of the accumulator `lut` by `lwe_in` on the scratch stack and hand it
+    /// to `output`.
+    fn blind_rotate<InputCont, F>(
+        &mut self,
+        lut: usize,
+        lwe_in: &LweCiphertext<InputCont>,
+        fourier_bsk: FourierLweBootstrapKeyView,
+        output: F,
+    ) where
+        InputCont: Container<Element = Scalar>,
+        F: FnOnce(GlweCiphertextView<'_, Scalar>),
+    {
+        assert_eq!(
+            lwe_in.lwe_size(),
+            fourier_bsk.input_lwe_dimension().to_lwe_size()
+        );
+        assert_eq!(fourier_bsk.glwe_size(), self.glwe_size);
+        assert_eq!(fourier_bsk.polynomial_size(), self.polynomial_size);
+
+        let PreparedLut {
+            accumulator,
+            log_lut_count,
+        } = &self.luts[lut];
+        let stack = self.buffers.stack();
+
+        let (mut local_accumulator_data, stack) =
+            stack.collect_aligned(CACHELINE_ALIGN, accumulator.as_ref().iter().copied());
+        let mut local_accumulator = GlweCiphertextMutView::from_container(
+            &mut *local_accumulator_data,
+            self.polynomial_size,
+            self.ciphertext_modulus,
+        );
+
+        gen_blind_rotate_local_assign(
+            fourier_bsk,
+            local_accumulator.as_mut_view(),
+            ModulusSwitchOffset(0),
+            *log_lut_count,
+            lwe_in.as_ref(),
+            self.fft.as_view(),
+            stack,
+        );
+        output(local_accumulator.as_view());
+    }
+}
+
+/// Same as [`programmable_bootstrap_lwe_ciphertext_many_lut`], writing the outputs as LWE
+/// ciphertexts sample-extracted from the rotated accumulator.
+pub fn programmable_bootstrap_lwe_ciphertext_many_lut_to_lwes<Scalar, InputCont, OutputCont>(
+    lwe_in: &LweCiphertext<InputCont>,
+    outputs: &mut [LweCiphertext<OutputCont>],
+    fourier_bsk: FourierLweBootstrapKeyView,
+    log_lut_count: LutCountLog,
+    message_modulus_log: usize,
+    ciphertext_modulus: CiphertextModulus<Scalar>,
+    f: &[LutFn<Scalar>],
+) where
+    Scalar: UnsignedTorus + CastInto<usize> + CastFrom<usize> + UnsignedInteger,
+    InputCont: Container<Element = Scalar>,
+    OutputCont: ContainerMut<Element = Scalar>,
+{
+    let mut bootstrapper = ManyLutBootstrapper::new(fourier_bsk, ciphertext_modulus);
+    let lut = bootstrapper.prepare(f, log_lut_count, message_modulus_log);
+    bootstrapper.bootstrap_into_lwes(lut, lwe_in, outputs, fourier_bsk);
+}
+
+#[cfg(test)]
+mod tests {
+    use jemalloc_ctl::thread;
+    use tfhe::core_crypto::prelude::{GlweCiphertextCount, encrypt_lwe_ciphertext};
+
+    use super::*;
+    use crate::algorithms::{
+        test_utils::new_test_context,
+        tools::{LutKind, make_f1_with_b},
+    };
+
+    #[test]
+    #[should_panic(expected = "one entry per box")]
+    fn test_prepare_rejects_short_table() {
+        let fourier_bsk = FourierLweBootstrapKey::new(
+            LweDimension(1),
+            GlweSize(2),
+            PolynomialSize(256),
+            DecompositionBaseLog(26),
+            DecompositionLevelCount(1),
+        );
+        let mut bootstrapper =
+            ManyLutBootstrapper::<u64>::new(fourier_bsk.as_view(), CiphertextModulus::new_native());
+        bootstrapper.prepare(&[LutFn::from_table(vec![0; 4])], LutCountLog(0), 3);
+    }
+
+    #[test]
+    fn test_many_lut_custom_functions() {
+        let message_modulus_log = 3;
+        let log_lut_count = LutCountLog(2);
+        let mut ctx = new_test_context(DecompositionBaseLog(4), DecompositionLevelCount(1));
+        let fourier_bsk = ctx.decomposer.fourier_bsk().as_view();
+        let delta_log = u64::BITS as usize - message_modulus_log - 1;
+        let half = 1_i64 << (message_modulus_log - 1);
+        let signed = move |x: u64| {
+            let x = x as i64;
+            if x >= half { x - 2 * half } else { x }
+        };
+        let encode = move |value: i64| (value as u64) << delta_log;
+
+        let sign = LutFn::from_fn(move |x| encode(signed(x).signum()));
+        let abs = LutFn::from_fn(move |x| encode(signed(x).abs()));
+        let msb = LutFn::from_table(
+            (0..1 << message_modulus_log)
+                .map(|x: u64| encode((x >> (message_modulus_log - 1)) as i64))
+                .collect(),
+        );
+        let identity = LutFn::new(LutKind::Custom(Box::new(move |x: u64| x << delta_log)), 0);
+        let f = [sign, abs, msb, identity];
+
+        for message in 0..1_u64 << message_modulus_log {
+            let mut lwe_in = LweCiphertext::new(
+                0u64,
+                fourier_bsk.input_lwe_dimension().to_lwe_size(),
+                CiphertextModulus::new_native(),
+            );
+            encrypt_lwe_ciphertext(
+                &ctx.lwe_key,
+                &mut lwe_in,
+                Plaintext(message << delta_log),
+                ctx.lwe_std_dev,
+                &mut ctx.encryption_generator,
+            );
+            let mut out_list = GlweCiphertextList::new(
+                0u64,
+                fourier_bsk.glwe_size(),
+                fourier_bsk.polynomial_size(),
+                GlweCiphertextCount(1 << log_lut_count.0),
+                CiphertextModulus::new_native(),
+            );
+            programmable_bootstrap_lwe_ciphertext_many_lut(
+                &lwe_in,
+                &mut out_list,
+                fourier_bsk,
+                log_lut_count,
+                message_modulus_log,
+                CiphertextModulus::new_native(),
+                &f,
+            );
+
+            let value = signed(message);
+            let expected = [
+                value.signum(),
+                value.abs(),
+                (message >> (message_modulus_log - 1)) as i64,
+                message as i64,
+            ];
+            for (i, glwe) in out_list.iter().enumerate() {
+                assert_eq!(
+                    ctx.decrypt_signed(&glwe, message_modulus_log + 1),
+                    expected[i],
+                    "function {i} of {message}"
+                );
+            }
+
+            let mut lwe_outs = vec![
+                LweCiphertext::new(
+                    0u64,
+                    fourier_bsk.output_lwe_dimension().to_lwe_size(),
+                    CiphertextModulus::new_native(),
+                );
+                1 << log_lut_count.0
+            ];
+            programmable_bootstrap_lwe_ciphertext_many_lut_to_lwes(
+                &lwe_in,
+                &mut lwe_outs,
+                fourier_bsk,
+                log_lut_count,
+                message_modulus_log,
+                CiphertextModulus::new_native(),
+                &f,
+            );
+            for (i, lwe) in lwe_outs.iter().enumerate() {
+                assert_eq!(
+                    ctx.decrypt_lwe_signed(lwe, message_modulus_log + 1),
+                    expected[i],
+                    "LWE output of function {i} of {message}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_bootstrap_into_does_not_allocate() {
+        let message_modulus_log = 3;
+        let mut ctx = new_test_context(DecompositionBaseLog(4), DecompositionLevelCount(1));
+        let fourier_bsk = ctx.decomposer.fourier_bsk().as_view();
+        let ciphertext_modulus = CiphertextModulus::new_native();
+        let mut bootstrapper = ManyLutBootstrapper::new(fourier_bsk, ciphertext_modulus);
+        let lut = bootstrapper.prepare(
+            &[make_f1_with_b(message_modulus_log + 1)],
+            LutCountLog(0),
+            message_modulus_log,
+        );
+
+        let mut lwe_in = LweCiphertext::new(
+            0u64,
+            fourier_bsk.input_lwe_dimension().to_lwe_size(),
+            ciphertext_modulus,
+        );
+        encrypt_lwe_ciphertext(
+            &ctx.lwe_key,
+            &mut lwe_in,
+            Plaintext(1 << 60),
+            ctx.lwe_std_dev,
+            &mut ctx.encryption_generator,
+        );
+        let mut out_list = GlweCiphertextList::new(
+            0u64,
+            fourier_bsk.glwe_size(),
+            fourier_bsk.polynomial_size(),
+            GlweCiphertextCount(2),
+            ciphertext_modulus,
+        );
+        bootstrapper.bootstrap_into(lut, &lwe_in, &mut out_list, fourier_bsk);
+
+        // fhe_processor installs jemalloc as the global allocator
+        let allocated = thread::allocatedp::mib().unwrap().read().unwrap();
+        let before = allocated.get();
+        for _ in 0..4 {
+            bootstrapper.bootstrap_into(lut, &lwe_in, &mut out_list, fourier_bsk);
+        }
+        assert_eq!(
+            allocated.get(),
+            before,
+            "bootstrap_into allocated on the heap"
+        );
     }
 }
@@ -0,0 +1,207 @@
+use concrete_fft::c64;
+use refined_tfhe_lhe::{
+    FftType, FourierGlweKeyswitchKey, allocate_and_generate_new_glwe_keyswitch_key,
+    convert_standard_glwe_keyswitch_key_to_fourier,
+};
+use tfhe::core_crypto::{
+    prelude::{
+        ActivatedRandomGenerator, CiphertextModulus, Container, DecompositionBaseLog,
+        DecompositionLevelCount, EncryptionRandomGenerator, FourierLweBootstrapKey, GlweCiphertext,
+        GlweDimension, GlweSecretKey, GlweSecretKeyOwned, GlweSize, LweCiphertext, LweDimension,
+        LweSecretKeyOwned, PlaintextCount, PlaintextList, PolynomialSize, SecretRandomGenerator,
+        SignedDecomposer, StandardDev, allocate_and_generate_new_binary_glwe_secret_key,
+        allocate_and_generate_new_binary_lwe_secret_key, decrypt_glwe_ciphertext,
+        decrypt_lwe_ciphertext, encrypt_glwe_ciphertext,
+        par_allocate_and_generate_new_lwe_bootstrap_key,
+        par_convert_standard_lwe_bootstrap_key_to_fourier,
+    },
+    seeders::new_seeder,
+};
+
+use crate::algorithms::decomposer::DigitDecomposer;
+
+/// Keys and decomposer shared by the encrypted tests, with the parameters of `main.rs`.
+pub(crate) struct TestContext<BskCont, KskCont>
+where
+    BskCont: Container<Element = c64>,
+    KskCont: Container<Element = c64>,
+{
+    pub glwe_key: GlweSecretKeyOwned<u64>,
+    /// Input key of the decomposer's bootstrapping key.
+    pub lwe_key: LweSecretKeyOwned<u64>,
+    pub encryption_generator: EncryptionRandomGenerator<ActivatedRandomGenerator>,
+    pub decomposer: DigitDecomposer<BskCont, KskCont>,
+    pub glwe_std_dev: StandardDev,
+    pub lwe_std_dev: StandardDev,
+}
+
+pub(crate) fn new_test_context(
+    base_log: DecompositionBaseLog,
+    level: DecompositionLevelCount,
+) -> TestContext<impl Container<Element = c64>, impl Container<Element = c64>> {
+    let mut boxed_seeder = new_seeder();
+    let seeder = boxed_seeder.as_mut();
+    let pbs_level = DecompositionLevelCount(1);
+    let pbs_base_log = DecompositionBaseLog(26);
+    let ks_level = DecompositionLevelCount(6);
+    let ks_base_log = DecompositionBaseLog(4);
+    let glwe_dimension = GlweDimension(1);
+    let polynomial_size = PolynomialSize(2048);
+    let lwe_dimension = LweDimension(1024);
+    let lwe_std_dev = StandardDev(7.50000e-08);
+    let glwe_std_dev = StandardDev(0.00000000000000029403601535432533);
+    let ciphertext_modulus = CiphertextModulus::<u64>::new_native();
+
+    let mut secret_generator =
+        SecretRandomGenerator::<ActivatedRandomGenerator>::new(seeder.seed());
+    let mut encryption_generator =
+        EncryptionRandomGenerator::<ActivatedRandomGenerator>::new(seeder.seed(), seeder);
+    let glwe_key = allocate_and_generate_new_binary_glwe_secret_key(
+        glwe_dimension,
+        polynomial_size,
+        &mut secret_generator,
+    );
+    let lwe_key =
+        allocate_and_generate_new_binary_lwe_secret_key(lwe_dimension, &mut secret_generator);
+
+    let lwe_glwe_key = GlweSecretKey::from_container(lwe_key.as_ref(), PolynomialSize(1024));
+    let glwe_glwe_key = GlweSecretKey::from_container(glwe_key.as_ref(), PolynomialSize(1024));
+
+    let ksk = allocate_and_generate_new_glwe_keyswitch_key(
+        &glwe_glwe_key,
+        &lwe_glwe_key,
+        ks_base_log,
+        ks_level,
+        lwe_std_dev,
+        ciphertext_modulus,
+        &mut encryption_generator,
+    );
+    let mut fourier_ksk = FourierGlweKeyswitchKey::new(
+        GlweSize(3),
+        GlweSize(2),
+        PolynomialSize(1024),
+        ks_base_log,
+        ks_level,
+        FftType::Vanilla,
+    );
+    convert_standard_glwe_keyswitch_key_to_fourier(&ksk, &mut fourier_ksk);
+
+    let bsk = par_allocate_and_generate_new_lwe_bootstrap_key(
+        &lwe_key,
+        &glwe_key,
+        pbs_base_log,
+        pbs_level,
+        glwe_std_dev,
+        ciphertext_modulus,
+        &mut encryption_generator,
+    );
+    let mut fourier_bsk = FourierLweBootstrapKey::new(
+        bsk.input_lwe_dimension(),
+        bsk.glwe_size(),
+        bsk.polynomial_size(),
+        bsk.decomposition_base_log(),
+        bsk.decomposition_level_count(),
+    );
+    par_convert_standard_lwe_bootstrap_key_to_fourier(&bsk, &mut fourier_bsk);
+
+    TestContext {
+        glwe_key,
+        lwe_key,
+        encryption_generator,
+        decomposer: DigitDecomposer::new(fourier_bsk, fourier_ksk, base_log, level),
+        glwe_std_dev,
+        lwe_std_dev,
+    }
+}
+
+impl<BskCont, KskCont> TestContext<BskCont, KskCont>
+where
+    BskCont: Container<Element = c64>,
+    KskCont: Container<Element = c64>,
+{
+    /// Encrypt `value` on the `n_bits` most significant bits of every slot.
+    pub fn encrypt(&mut self, value: u64, n_bits: usize) -> GlweCiphertext<Vec<u64>> {
+        let polynomial_size = self.glwe_key.polynomial_size();
+        let mut input = GlweCiphertext::new(
+            0u64,
+            self.glwe_key.glwe_dimension().to_glwe_size(),
+            polynomial_size,
+            CiphertextModulus::new_native(),
+        );
+        let plain_list =
+            PlaintextList::from_container(vec![value << (64 - n_bits); polynomial_size.0]);
+        encrypt_glwe_ciphertext(
+            &self.glwe_key,
+            &mut input,
+            &plain_list,
+            self.glwe_std_dev,
+            &mut self.encryption_generator,
+        );
+        input
+    }
+
+    /// Encrypt `values[i]` on the `n_bits` most significant bits of slot `i`, the remaining
+    /// slots being zero.
+    pub fn encrypt_slots(&mut self, values: &[u64], n_bits: usize) -> GlweCiphertext<Vec<u64>> {
+        let polynomial_size = self.glwe_key.polynomial_size();
+        let mut input = GlweCiphertext::new(
+            0u64,
+            self.glwe_key.glwe_dimension().to_glwe_size(),
+            polynomial_size,
+            CiphertextModulus::new_native(),
+        );
+        let mut plain = vec![0u64; polynomial_size.0];
+        for (p, value) in plain.iter_mut().zip(values.iter()) {
+            *p = value << (64 - n_bits);
+        }
+        let plain_list = PlaintextList::from_container(plain);
+        encrypt_glwe_ciphertext(
+            &self.glwe_key,
+            &mut input,
+            &plain_list,
+            self.glwe_std_dev,
+            &mut self.encryption_generator,
+        );
+        input
+    }
+
+    /// Decrypt the constant coefficient of `glwe` as a signed value of `n_bits` bits stored at
+    /// scale `2^(64 - n_bits)`.
+    pub fn decrypt_signed<Cont>(&self, glwe: &GlweCiphertext<Cont>, n_bits: usize) -> i64
+    where
+        Cont: Container<Element = u64>,
+    {
+        let mut plain_list =
+            PlaintextList::new(0u64, PlaintextCount(self.glwe_key.polynomial_size().0));
+        decrypt_glwe_ciphertext(&self.glwe_key, glwe, &mut plain_list);
+        let decomposer =
+            SignedDecomposer::<u64>::new(DecompositionBaseLog(n_bits), DecompositionLevelCount(1));
+        let decoded = decomposer.closest_representable(*plain_list.get(0).0);
+        (decoded as i64) >> (64 - n_bits)
+    }
+
+    /// Same as [`TestContext::decrypt_signed`] for an LWE under the GLWE key.
+    pub fn decrypt_lwe_signed<Cont>(&self, lwe: &LweCiphertext<Cont>, n_bits: usize) -> i64
+    where
+        Cont: Container<Element = u64>,
+    {
+        let plain = decrypt_lwe_ciphertext(&self.glwe_key.as_lwe_secret_key(), lwe);
+        let decomposer =
+            SignedDecomposer::<u64>::new(DecompositionBaseLog(n_bits), DecompositionLevelCount(1));
+        let decoded = decomposer.closest_representable(plain.0);
+        (decoded as i64) >> (64 - n_bits)
+    }
+
+    /// Decrypt the chunks of a table lookup, most significant chunk first, each encoded on
+    /// `message_size` bits with one bit of padding.
+    pub fn decrypt_chunks<Cont>(&self, chunks: &[LweCiphertext<Cont>], message_size: usize) -> u64
+    where
+        Cont: Container<Element = u64>,
+    {
+        chunks.iter().fold(0, |acc, lwe| {
+            let plain = decrypt_lwe_ciphertext(&self.glwe_key.as_lwe_secret_key(), lwe).0;
+            let chunk = ((plain >> (64 - message_size - 2)) + 1) >> 1;
+            (acc << message_size) | (chunk % (1 << message_size))
+        })
+    }
+}
@@ -1,4 +1,4 @@
-use tfhe::core_crypto::prelude::{CastFrom, UnsignedTorus};
+use tfhe::core_crypto::prelude::{CastFrom, CastInto, UnsignedTorus};
 
 // pub fn make_f1_with_b<Scalar>(b: usize) -> impl Fn(Scalar) -> Scalar
 // where
@@ -30,55 +30,78 @@ use tfhe::core_crypto::prelude::{CastFrom, UnsignedTorus};
 //         }
 //     }
 // }
-#[derive(Copy, Clone)]
-enum LutKind {
+/// PBS 中每个盒子上求值的函数
+pub enum LutKind<Scalar> {
     F1, // 对应 make_f1_with_b
     F3, // 对应 make_f3_with_b
+    /// 任意函数：输入为盒子下标，输出为编码后的明文
+    Custom(Box<dyn Fn(Scalar) -> Scalar + Send + Sync>),
+    /// 查表：第 i 个盒子取 table[i]，长度必须等于盒子个数
+    Table(Vec<Scalar>),
 }
 
 /// 统一的可调用对象：在构造时把与 b 有关的量预计算好
-pub struct LutFn {
-    kind: LutKind,
+pub struct LutFn<Scalar> {
+    kind: LutKind<Scalar>,
     b: usize,
 }
 
-impl LutFn {
-    /// 构造函数（通用）
-    pub fn new<Scalar>(kind: LutKind, b: usize) -> Self
+impl<Scalar> LutFn<Scalar>
+where
+    Scalar: UnsignedTorus + CastFrom<usize> + CastInto<usize> + Copy,
+{
+    /// 构造函数（通用），`b` 只被 [`LutKind::F1`] 和 [`LutKind::F3`] 使用
+    pub fn new(kind: LutKind<Scalar>, b: usize) -> Self {
+        LutFn { kind, b }
+    }
+
+    /// 在盒子下标上求值任意函数 `f`
+    pub fn from_fn<F>(f: F) -> Self
     where
-        Scalar: UnsignedTorus + CastFrom<usize> + Copy,
+        F: Fn(Scalar) -> Scalar + Send + Sync + 'static,
     {
+        Self::new(LutKind::Custom(Box::new(f)), 0)
+    }
 
-        LutFn { kind, b }
+    /// 第 i 个盒子取 `table[i]`
+    pub fn from_table(table: Vec<Scalar>) -> Self {
+        Self::new(LutKind::Table(table), 0)
     }
 
     /// 统一的调用接口 —— 运行时只用 x
-    pub fn call<Scalar>(&self, x: Scalar) -> Scalar
-    where
-        Scalar: UnsignedTorus + CastFrom<usize> + Copy,
-    {
-        match self.kind {
+    pub fn call(&self, x: Scalar) -> Scalar {
+        match &self.kind {
             LutKind::F1 => {
                 Scalar::ONE.wrapping_neg() << Scalar::BITS - self.b - 2
             }
             LutKind::F3 => {
                 (Scalar::TWO * x + Scalar::ONE).wrapping_neg()
             }
+            LutKind::Custom(f) => f(x),
+            LutKind::Table(table) => table[x.cast_into()],
+        }
+    }
+
+    /// [`LutKind::Table`] 的表长，其他种类返回 `None`
+    pub fn table_len(&self) -> Option<usize> {
+        match &self.kind {
+            LutKind::Table(table) => Some(table.len()),
+            _ => None,
         }
     }
 }
 
 /// 工厂函数：返回同一具体类型 LutFn<Scalar>
-pub fn make_f1_with_b<Scalar>(b: usize) -> LutFn
+pub fn make_f1_with_b<Scalar>(b: usize) -> LutFn<Scalar>
 where
-    Scalar: UnsignedTorus + CastFrom<usize> + Copy,
+    Scalar: UnsignedTorus + CastFrom<usize> + CastInto<usize> + Copy,
 {
-    LutFn::new::<Scalar>(LutKind::F1, b)
+    LutFn::new(LutKind::F1, b)
 }
 
-pub fn make_f3_with_b<Scalar>(b: usize) -> LutFn
+pub fn make_f3_with_b<Scalar>(b: usize) -> LutFn<Scalar>
 where
-    Scalar: UnsignedTorus + CastFrom<usize> + Copy,
+    Scalar: UnsignedTorus + CastFrom<usize> + CastInto<usize> + Copy,
 {
-    LutFn::new::<Scalar>(LutKind::F3, b)
+    LutFn::new(LutKind::F3, b)
 }
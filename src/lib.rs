@@ -0,0 +1,5 @@
+pub mod algorithms;
+
+pub use algorithms::decomposer::DigitDecomposer;
+pub use algorithms::digit_decompose::{DecomposeBuffers, DigitMode, DigitOrder};
+pub use algorithms::tools::{LutFn, LutKind};
@@ -4,35 +4,9 @@ use std::{
     vec,
 };
 
-use concrete_fft::c64;
-use fhe_processor::{
-    operations::{
-        cipher_lut::generate_lut_from_vecs_auto, manager::concat_ggsw_lists,
-        operation::horizontal_vertical_packing_without_extract,
-        plain_lut::split_adjusted_lut_by_chunk,
-    },
-    processors::{
-        cbs_4_bits::circuit_bootstrapping_4_bits_at_once_rev_tr,
-        key_gen::allocate_and_generate_new_reused_lwe_key,
-        lwe_stored_ksk::allocate_and_generate_new_stored_reused_lwe_keyswitch_key,
-        lwe_storede_ks::stored_reused_keyswitch_lwe_ciphertext,
-    },
-    utils::instance::SetI,
-};
+use fhe_processor::utils::instance::SetI;
 use itertools::izip;
-use rayon::{
-    iter::{IndexedParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator},
-    slice::ParallelSliceMut,
-};
-use refined_tfhe_lhe::{
-    FourierGlweKeyswitchKey, allocate_and_generate_new_glwe_keyswitch_key, gen_all_auto_keys,
-    generate_scheme_switching_key,
-};
-use tfhe::core_crypto::{
-    fft_impl::fft64::crypto::wop_pbs::vertical_packing_scratch,
-    prelude::programmable_bootstrap_lwe_ciphertext,
-};
-
+use refined_tfhe_lhe::{FourierGlweKeyswitchKey, allocate_and_generate_new_glwe_keyswitch_key};
 use tfhe::{
     boolean::prelude::{
         DecompositionBaseLog, DecompositionLevelCount, GlweDimension, LweDimension, PolynomialSize,
@@ -40,16 +14,10 @@ use tfhe::{
     },
     core_crypto::{
         prelude::{
-            ActivatedRandomGenerator, CiphertextModulus, Cleartext, ComputationBuffers,
-            ContiguousEntityContainer, ContiguousEntityContainerMut, EncryptionRandomGenerator,
-            Fft, FourierGgswCiphertextList, FourierLweBootstrapKey, GlweCiphertext,
-            GlweCiphertextCount, GlweCiphertextList, GlweSecretKey, GlweSize, LweCiphertext,
-            LweSize, MonomialDegree, PlaintextList, SecretRandomGenerator, SignedDecomposer,
-            allocate_and_generate_new_binary_glwe_secret_key,
-            allocate_and_generate_new_binary_lwe_secret_key,
-            allocate_and_generate_new_lwe_bootstrap_key,
-            convert_standard_lwe_bootstrap_key_to_fourier, encrypt_glwe_ciphertext,
-            extract_lwe_sample_from_glwe_ciphertext, glwe_ciphertext_cleartext_mul_assign,
+            ActivatedRandomGenerator, CiphertextModulus, EncryptionRandomGenerator,
+            FourierLweBootstrapKey, GlweCiphertext, GlweSecretKey, GlweSize, LweCiphertext,
+            PlaintextList, SecretRandomGenerator, allocate_and_generate_new_binary_glwe_secret_key,
+            allocate_and_generate_new_binary_lwe_secret_key, encrypt_glwe_ciphertext,
             par_allocate_and_generate_new_lwe_bootstrap_key,
             par_convert_standard_lwe_bootstrap_key_to_fourier,
         },
@@ -57,9 +25,12 @@ use tfhe::{
     },
 };
 
-use crate::algorithms::digit_decompose::*;
+use digit_decompose::algorithms::{
+    cbs_with_decompose::{CbsKeys, CipherLut, digits_to_ggsw_bits, vertical_packing_lookup},
+    digit_recompose::{RecomposeKey, digit_recompose_lwes},
+};
+use digit_decompose::{DigitDecomposer, DigitOrder};
 
-mod algorithms;
 fn main() {
     println!("Hello, world!");
     let decompose_levels = vec![
@@ -151,99 +122,19 @@ fn main() {
         bsk.decomposition_level_count(),
     );
     par_convert_standard_lwe_bootstrap_key_to_fourier(&bsk, &mut fourier_bsk);
+    let mut decomposer = DigitDecomposer::new(
+        fourier_bsk,
+        fourier_ksk,
+        decompose_base_log,
+        decompose_levels[0],
+    );
+    // vertical packing 按高位在前的顺序使用选择位
+    decomposer.set_order(DigitOrder::MsbFirst);
 
     ////////////////////////////////////////////////////////////////////////////////
     let cbs_params = *SetI;
-    let cbs_lwe_dimension = cbs_params.lwe_dimension();
-    let cbs_lwe_modular_std_dev = cbs_params.lwe_modular_std_dev();
-    let cbs_polynomial_size = cbs_params.polynomial_size();
-    let cbs_glwe_dimension = cbs_params.glwe_dimension();
-    let cbs_glwe_modular_std_dev = cbs_params.glwe_modular_std_dev();
-    let cbs_pbs_base_log = cbs_params.pbs_base_log();
-    let cbs_pbs_level = cbs_params.pbs_level();
-    let cbs_ks_base_log = cbs_params.ks_base_log();
-    let cbs_ks_level = cbs_params.ks_level();
-    let cbs_auto_base_log = cbs_params.auto_base_log();
-    let cbs_auto_level = cbs_params.auto_level();
-    let cbs_auto_fft_type = cbs_params.fft_type_auto();
-    let cbs_ss_base_log = cbs_params.ss_base_log();
-    let cbs_ss_level = cbs_params.ss_level();
-    let cbs_base_log = cbs_params.cbs_base_log();
-    let cbs_level = cbs_params.cbs_level();
-    let cbs_ciphertext_modulus = cbs_params.ciphertext_modulus();
-    let cbs_message_size = cbs_params.message_size();
-    let cbs_extract_size = cbs_params.extract_size();
-    let cbs_glwe_size = cbs_glwe_dimension.to_glwe_size();
-    let cbs_glwe_lwe_size = LweSize(cbs_glwe_dimension.0 * cbs_polynomial_size.0 + 1);
-
-    let glwe_lwe_sk = glwe_key.as_lwe_secret_key();
-    let cbs_glwe_key =
-        GlweSecretKey::from_container(glwe_key.as_ref().to_vec(), cbs_polynomial_size).to_owned();
-    let lwe_sk_after_ks = allocate_and_generate_new_reused_lwe_key(&glwe_lwe_sk, cbs_lwe_dimension);
-    let cbs_ksk = allocate_and_generate_new_stored_reused_lwe_keyswitch_key(
-        &glwe_lwe_sk,
-        &lwe_sk_after_ks,
-        cbs_ks_base_log,
-        cbs_ks_level,
-        cbs_lwe_modular_std_dev,
-        cbs_ciphertext_modulus,
-        &mut encryption_generator,
-    );
-
-    let cbs_bsk = allocate_and_generate_new_lwe_bootstrap_key(
-        &lwe_sk_after_ks,
-        &cbs_glwe_key,
-        cbs_pbs_base_log,
-        cbs_pbs_level,
-        cbs_glwe_modular_std_dev,
-        cbs_ciphertext_modulus,
-        &mut encryption_generator,
-    );
-
-    let mut cbs_fourier_bsk = FourierLweBootstrapKey::new(
-        cbs_bsk.input_lwe_dimension(),
-        cbs_bsk.glwe_size(),
-        cbs_bsk.polynomial_size(),
-        cbs_bsk.decomposition_base_log(),
-        cbs_bsk.decomposition_level_count(),
-    );
-    convert_standard_lwe_bootstrap_key_to_fourier(&cbs_bsk, &mut cbs_fourier_bsk);
-    drop(cbs_bsk);
-
-    let auto_keys = gen_all_auto_keys(
-        cbs_auto_base_log,
-        cbs_auto_level,
-        cbs_auto_fft_type,
-        &cbs_glwe_key,
-        cbs_glwe_modular_std_dev,
-        &mut encryption_generator,
-    );
-
-    let ss_key = generate_scheme_switching_key(
-        &cbs_glwe_key,
-        cbs_ss_base_log,
-        cbs_ss_level,
-        cbs_glwe_modular_std_dev,
-        ciphertext_modulus,
-        &mut encryption_generator,
-    );
-
-    let fourier_ggsw_list = FourierGgswCiphertextList::new(
-        vec![
-            c64::default();
-            cbs_extract_size
-                * cbs_polynomial_size.to_fourier_polynomial_size().0
-                * cbs_glwe_size.0
-                * cbs_glwe_size.0
-                * cbs_level.0
-        ],
-        cbs_extract_size,
-        cbs_glwe_size,
-        cbs_polynomial_size,
-        cbs_base_log,
-        cbs_level,
-    );
-    let mut extract_input = LweCiphertext::new(0_u64, cbs_glwe_lwe_size, ciphertext_modulus);
+    let cbs_keys = CbsKeys::new(&glwe_key, cbs_params, &mut encryption_generator);
+    let extract_input = LweCiphertext::new(0_u64, cbs_keys.output_lwe_size(), ciphertext_modulus);
 
     let mut input = GlweCiphertext::new(
         0u64,
@@ -257,33 +148,22 @@ fn main() {
         combine_levels.iter(),
         combine_base_logs.iter()
     ) {
-        let combine_bsk = allocate_and_generate_new_lwe_bootstrap_key(
-            &lwe_key,
-            &cbs_glwe_key,
+        let recompose_key = RecomposeKey::new(
+            &glwe_key,
+            &cbs_params,
             *combine_base_log,
             *combine_level,
-            cbs_glwe_modular_std_dev,
-            ciphertext_modulus,
             &mut encryption_generator,
         );
-        let fourier_combine_bsk = {
-            let mut tmp = FourierLweBootstrapKey::new(
-                combine_bsk.input_lwe_dimension(),
-                combine_bsk.glwe_size(),
-                combine_bsk.polynomial_size(),
-                combine_bsk.decomposition_base_log(),
-                combine_bsk.decomposition_level_count(),
-            );
-            convert_standard_lwe_bootstrap_key_to_fourier(&combine_bsk, &mut tmp);
-            drop(combine_bsk);
-            tmp
-        };
+        decomposer.set_level_count(*decompose_level);
+        // 每个层数只分配一次分解用的缓冲区和输出
+        let mut buffers = decomposer.allocate_buffers(ciphertext_modulus);
+        let mut output = decomposer.allocate_output(&input);
+        // 查表时间与函数无关，这里用恒等函数
+        let n_bits = decompose_base_log.0 * decompose_level.0;
+        let plain_lut = CipherLut::from_fn(|x| x, n_bits, n_bits, &cbs_params);
         let mut final_lwes = vec![extract_input.clone(); decompose_level.0];
-        let mut middle_lwe =
-            LweCiphertext::new(0_u64, cbs_lwe_dimension.to_lwe_size(), ciphertext_modulus);
-        let mut final_lwe = extract_input.clone();
-        let combine_lut =
-            GlweCiphertext::new(0u64, cbs_glwe_size, cbs_polynomial_size, ciphertext_modulus);
+        let chunk_widths = vec![cbs_params.message_size(); decompose_level.0];
         let mut decompose_time = Duration::ZERO;
         let mut lut_time = Duration::ZERO;
         let mut group_time = Duration::ZERO;
@@ -308,158 +188,25 @@ fn main() {
                 &mut encryption_generator,
             );
 
-            let mut output = GlweCiphertextList::new(
-                0u64,
-                glwe_dimension.to_glwe_size(),
-                polynomial_size,
-                GlweCiphertextCount(decompose_level.0),
-                ciphertext_modulus,
-            );
-
-            let mut fourier_ggsw_lists = vec![fourier_ggsw_list.clone(); decompose_level.0];
-
-            // digit_decompose_no_padding_ori(
-            //     &input,
-            //     &mut output,
-            //     decompose_base_log,
-            //     decompose_level,
-            //     &fourier_bsk,
-            //     &ksk_lwe,
-            //     &glwe_key,
-            // );
-            // digit_decompose_no_padding(
-            //     &input,
-            //     &mut output,
-            //     decompose_base_log,
-            //     decompose_level,
-            //     &fourier_bsk,
-            //     &fourier_ksk,
-            //     &glwe_key,
-            // );
-
-            // for (i,mut e) in output.iter_mut().enumerate() {
-            //     // decrypt_glwe_ciphertext(&glwe_key, &e, &mut plain_list);
-            //     // let decode = decomposer.closest_representable(*plain_list.get(0).0);
-            //     // print!(" {:04b}", decode >> 60);
-            //     let p = num >> (i * 4) & 0b1111;
-            //     let local_plain =
-            //     PlaintextList::from_container(vec![(p as u64) << 60; polynomial_size.0]);
-            //     encrypt_glwe_ciphertext(
-            //         &glwe_key,
-            //         &mut e,
-            //         &local_plain,
-            //         glwe_std_dev,
-            //         &mut encryption_generator,
-            //     );
-            // }
             let start = Instant::now();
-            digit_decompose_with_padding(
-                &input,
-                &mut output,
-                decompose_base_log,
-                *decompose_level,
-                &fourier_bsk,
-                &fourier_ksk,
-                &glwe_key,
-            );
+            decomposer.decompose_with(&input, &mut output, &mut buffers);
             let duration = start.elapsed();
             decompose_time.add_assign(duration);
             let start = Instant::now();
-            for mut e in output.iter_mut() {
-                glwe_ciphertext_cleartext_mul_assign(&mut e, Cleartext(2_u64));
-            }
-
-            // for (i, mut e) in output.iter().zip(fourier_ggsw_lists.iter_mut()) {
-            //     extract_lwe_sample_from_glwe_ciphertext(&i, &mut extract_input, MonomialDegree(0));
-            //     circuit_bootstrapping_4_bits_at_once_rev_tr(
-            //         &mut extract_input,
-            //         &mut e,
-            //         cbs_fourier_bsk.as_view(),
-            //         &auto_keys,
-            //         ss_key.as_view(),
-            //         &cbs_ksk,
-            //         &cbs_params,
-            //     );
-            // }
-            fourier_ggsw_lists
-                .par_iter_mut()
-                .enumerate().zip(output.par_iter())
-                .for_each(|((i, ggsw), e)| {
-                    let mut temp_buffer = extract_input.clone();
-                    extract_lwe_sample_from_glwe_ciphertext(&e, &mut temp_buffer, MonomialDegree(0));
-                    circuit_bootstrapping_4_bits_at_once_rev_tr(
-                        &temp_buffer,
-                        ggsw,
-                        cbs_fourier_bsk.as_view(),
-                        &auto_keys,
-                        ss_key.as_view(),
-                        &cbs_ksk,
-                        &cbs_params,
-                    );
-                });
-            fourier_ggsw_lists.reverse();
-            let ggsw_bits = concat_ggsw_lists(fourier_ggsw_lists, true);
-
-            // a trival lut, just ues it size
-            let n_bits = decompose_base_log.0 * decompose_level.0;
-            let plain_lut = vec![0usize; 1 << n_bits];
-            let split_plain_lut =
-                split_adjusted_lut_by_chunk(&plain_lut, n_bits, decompose_base_log.0);
-            let (all_lut, pack_size) = generate_lut_from_vecs_auto(
-                &split_plain_lut,
-                cbs_polynomial_size,
-                1 << (u64::BITS as usize - cbs_message_size),
-            );
-            let ggsw_view = ggsw_bits.as_view();
-            let group_size = pack_size.min(n_bits / 4);
-            let lut_size = 1_usize << n_bits;
-            let binding = Fft::new(cbs_polynomial_size);
-            let fft_view = binding.as_view();
-            all_lut
-                .par_iter()
-                .zip(final_lwes.par_chunks_mut(group_size))
-                .for_each(|(lut, lwe_group)| {
-                    let mut local_buffer = ComputationBuffers::new();
-                    let need = vertical_packing_scratch::<u64>(
-                        ggsw_view.glwe_size(),
-                        ggsw_view.polynomial_size(),
-                        lut.polynomial_count(),
-                        ggsw_view.count(),
-                        fft_view,
-                    )
-                    .unwrap()
-                    .unaligned_bytes_required();
-                    local_buffer.resize(need);
-
-                    let stack = local_buffer.stack();
-                    let temp = horizontal_vertical_packing_without_extract(
-                        lut.as_view(),
-                        ggsw_view,
-                        fft_view,
-                        stack,
-                        lwe_group[0].ciphertext_modulus(),
-                    );
-                    for (i, lwe) in lwe_group.iter_mut().enumerate() {
-                        extract_lwe_sample_from_glwe_ciphertext(
-                            &temp,
-                            lwe,
-                            MonomialDegree(i * lut_size),
-                        );
-                    }
-                });
+            let ggsw_bits = digits_to_ggsw_bits(&output, decomposer.order(), &cbs_keys)
+                .expect("CbsKeys holds every automorphism key of the trace");
+            vertical_packing_lookup(&ggsw_bits, &plain_lut, &mut final_lwes, cbs_keys.fft());
             let duration = start.elapsed();
             lut_time.add_assign(duration);
 
             let start = Instant::now();
-            for elem in final_lwes.iter_mut() {
-                stored_reused_keyswitch_lwe_ciphertext(&cbs_ksk, &elem, &mut middle_lwe);
-                programmable_bootstrap_lwe_ciphertext(
-                    &middle_lwe,
-                    &mut final_lwe,
-                    &combine_lut,
-                    &fourier_combine_bsk,
-                );
-            }
+            let _final_lwe = digit_recompose_lwes(
+                &final_lwes,
+                &chunk_widths,
+                DigitOrder::MsbFirst,
+                &cbs_keys,
+                &recompose_key,
+            );
             let duration = start.elapsed();
             group_time.add_assign(duration);
 